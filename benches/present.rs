@@ -0,0 +1,101 @@
+// Compares presenting the SDL screen after every instruction with presenting once per 60 Hz frame
+// on a sprite heavy program. Runs on SDL's dummy video driver so no display is needed:
+//
+//     cargo bench --bench present
+
+use std::time::{Duration, Instant};
+
+use chip8::audible::Audible;
+use chip8::constants::{SCREEN_WIDTH, SCREEN_HEIGHT, INSTRUCTIONS_PER_FRAME};
+use chip8::cpu::Cpu;
+use chip8::drawable::Drawable;
+use chip8::palette::CLASSIC_GREEN;
+use chip8::rom::{Rom, DEFAULT_LOAD_ADDRESS};
+use chip8::sdl_screen::SDLScreen;
+use sdl2::render::Canvas;
+use sdl2::video::Window;
+
+const FRAMES: usize = 600;
+
+// Draws 15 line sprites in an endless diagonal sweep, so nearly every instruction touches the screen
+const PROGRAM: [u8; 14] = [
+    0xA2, 0x20, // LD I, 0x220
+    0x60, 0x00, // LD V0, 0
+    0x61, 0x00, // LD V1, 0
+    0xD0, 0x1F, // DRW V0, V1, 15
+    0x70, 0x08, // ADD V0, 8
+    0x71, 0x03, // ADD V1, 3
+    0x12, 0x06, // JP 0x206
+];
+const SPRITE_OFFSET: usize = 0x20;
+
+struct Silence;
+
+impl Audible for Silence {
+    fn enable_sound(&mut self, _frame: u64) {}
+    fn disable_sound(&mut self, _frame: u64) {}
+}
+
+fn create_canvas(sdl_context: &sdl2::Sdl) -> Canvas<Window> {
+    let video_subsystem = sdl_context.video().unwrap();
+    let window = video_subsystem.window("CHIP-8 bench", SCREEN_WIDTH as u32 * 20, SCREEN_HEIGHT as u32 * 20)
+    .hidden()
+    .build().unwrap();
+
+    window.into_canvas().software().build().unwrap()
+}
+
+fn create_cpu() -> Cpu {
+    let mut data = vec![0; SPRITE_OFFSET + 15];
+    data[..PROGRAM.len()].copy_from_slice(&PROGRAM);
+    data[SPRITE_OFFSET..].fill(0xFF);
+
+    let mut cpu = Cpu::new();
+    cpu.load(&Rom::from_bytes(data, DEFAULT_LOAD_ADDRESS).unwrap()).unwrap();
+
+    cpu
+}
+
+fn run(screen: &mut SDLScreen, present_every_instruction: bool) -> Duration {
+    let mut cpu = create_cpu();
+    let mut audio = Silence;
+    let pressed_keys = [0; 16];
+    let mut last_key = 255;
+
+    screen.cls();
+
+    let start = Instant::now();
+    for _ in 0..FRAMES {
+        cpu.tick(&mut audio);
+
+        for _ in 0..INSTRUCTIONS_PER_FRAME {
+            cpu.step(screen, &mut audio, &pressed_keys, &mut last_key).unwrap();
+
+            if present_every_instruction {
+                screen.present();
+            }
+        }
+
+        if !present_every_instruction {
+            screen.present();
+        }
+    }
+
+    start.elapsed()
+}
+
+fn main() {
+    sdl2::hint::set("SDL_VIDEODRIVER", "dummy");
+    let sdl_context = sdl2::init().unwrap();
+    let canvas = create_canvas(&sdl_context);
+    let texture_creator = canvas.texture_creator();
+    let mut screen = SDLScreen::new(canvas, &texture_creator, CLASSIC_GREEN);
+
+    let per_instruction = run(&mut screen, true);
+    let per_frame = run(&mut screen, false);
+
+    println!("{FRAMES} frames, {INSTRUCTIONS_PER_FRAME} instructions per frame");
+    println!("present after every instruction: {per_instruction:?}");
+    println!("present once per frame:          {per_frame:?}");
+    println!("speedup: {:.1}x", per_instruction.as_secs_f64() / per_frame.as_secs_f64());
+}
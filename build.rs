@@ -1,3 +1,7 @@
 fn main() {
-    println!(r"cargo:rustc-link-search=/opt/homebrew/lib");
-}
\ No newline at end of file
+    // Homebrew on Apple silicon installs SDL2 outside the default linker search path. Checked on
+    // the target rather than with cfg! so cross compiling from macOS gets it right
+    if std::env::var("CARGO_CFG_TARGET_OS").as_deref() == Ok("macos") {
+        println!(r"cargo:rustc-link-search=/opt/homebrew/lib");
+    }
+}
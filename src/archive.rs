@@ -0,0 +1,191 @@
+use std::io::{Cursor, Read};
+use std::path::Path;
+
+use flate2::read::GzDecoder;
+use zip::ZipArchive;
+
+use crate::rom::RomError;
+
+const ZIP_MAGIC: [u8; 4] = [0x50, 0x4B, 0x03, 0x04];
+// Gzip magic plus the deflate compression method, the only one in use
+const GZIP_MAGIC: [u8; 3] = [0x1F, 0x8B, 0x08];
+
+// Extensions that mark an archive entry as a ROM when picking one automatically, Octo cartridges
+// included
+const ROM_EXTENSIONS: [&str; 7] = ["ch8", "c8", "sc8", "xo8", "rom", "bin", "gif"];
+// Most that is unpacked. Far more than any ROM or Octo cartridge, whether the program fits in
+// memory is up to Rom::from_bytes
+const MAX_UNPACKED_SIZE: usize = 16 << 20;
+
+// Returns the program inside zip or gzip data, anything else is passed through as a plain ROM.
+// The format is recognised by its magic bytes, so it doesn't matter where the data came from
+pub fn unpack(data: Vec<u8>, entry: Option<&str>) -> Result<Vec<u8>, RomError> {
+    if data.starts_with(&ZIP_MAGIC) {
+        unzip(data, entry)
+    } else if data.starts_with(&GZIP_MAGIC) {
+        read_limited(GzDecoder::new(data.as_slice()))
+    } else {
+        Ok(data)
+    }
+}
+
+fn unzip(data: Vec<u8>, entry: Option<&str>) -> Result<Vec<u8>, RomError> {
+    let mut archive = ZipArchive::new(Cursor::new(data)).map_err(|e| RomError::Archive(e.to_string()))?;
+
+    let files: Vec<String> = archive.file_names()
+        .filter(|name| !name.ends_with('/'))
+        .map(String::from)
+        .collect();
+
+    let name = match entry {
+        Some(entry) => find_entry(files, entry)?,
+        None => pick_rom(files)?,
+    };
+
+    let file = archive.by_name(&name).map_err(|e| RomError::Archive(e.to_string()))?;
+    read_limited(file)
+}
+
+// Unpacks no more than MAX_UNPACKED_SIZE, so a small archive that inflates to gigabytes is
+// rejected before it is held in memory
+fn read_limited<R: Read>(reader: R) -> Result<Vec<u8>, RomError> {
+    let mut unpacked = vec![];
+    reader.take(MAX_UNPACKED_SIZE as u64 + 1).read_to_end(&mut unpacked)?;
+
+    if unpacked.len() > MAX_UNPACKED_SIZE {
+        return Err(RomError::Archive(format!("unpacks to more than {MAX_UNPACKED_SIZE} bytes")));
+    }
+
+    Ok(unpacked)
+}
+
+// The entry with exactly that path, or else the only one with that file name in any directory
+fn find_entry(files: Vec<String>, entry: &str) -> Result<String, RomError> {
+    if files.iter().any(|name| name == entry) {
+        return Ok(entry.to_string());
+    }
+
+    let mut matches: Vec<String> = files.into_iter().filter(|name| file_name(name) == entry).collect();
+    matches.sort();
+    match matches.len() {
+        0 => Err(RomError::EntryNotFound(entry.to_string())),
+        1 => Ok(matches.remove(0)),
+        _ => Err(RomError::AmbiguousEntry(matches)),
+    }
+}
+
+// Picks the only ROM in the archive, or the only file if none of them looks like a ROM
+fn pick_rom(mut files: Vec<String>) -> Result<String, RomError> {
+    let roms: Vec<String> = files.iter()
+        .filter(|name| {
+            let extension = Path::new(name).extension().and_then(|e| e.to_str()).unwrap_or("");
+            ROM_EXTENSIONS.contains(&extension.to_ascii_lowercase().as_str())
+        })
+        .cloned()
+        .collect();
+
+    if !roms.is_empty() {
+        files = roms;
+    }
+
+    files.sort();
+    match files.len() {
+        0 => Err(RomError::Archive("archive has no files".to_string())),
+        1 => Ok(files.remove(0)),
+        _ => Err(RomError::AmbiguousEntry(files)),
+    }
+}
+
+fn file_name(path: &str) -> &str {
+    path.rsplit('/').next().unwrap_or(path)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    use flate2::Compression;
+    use flate2::write::GzEncoder;
+    use zip::write::{FileOptions, ZipWriter};
+
+    fn zip(files: &[(&str, &[u8])]) -> Vec<u8> {
+        let mut writer = ZipWriter::new(Cursor::new(vec![]));
+        for (name, data) in files {
+            writer.start_file(*name, FileOptions::default()).unwrap();
+            writer.write_all(data).unwrap();
+        }
+
+        writer.finish().unwrap().into_inner()
+    }
+
+    #[test]
+    fn plain_rom_is_passed_through() {
+        assert_eq!(unpack(vec![0x00, 0xE0], None).unwrap(), [0x00, 0xE0]);
+    }
+
+    #[test]
+    fn gzip() {
+        let mut encoder = GzEncoder::new(vec![], Compression::default());
+        encoder.write_all(&[0x12, 0x00]).unwrap();
+
+        assert_eq!(unpack(encoder.finish().unwrap(), None).unwrap(), [0x12, 0x00]);
+    }
+
+    #[test]
+    fn gzip_larger_than_the_limit() {
+        let mut encoder = GzEncoder::new(vec![], Compression::default());
+        encoder.write_all(&vec![0; MAX_UNPACKED_SIZE + 1]).unwrap();
+
+        assert!(matches!(unpack(encoder.finish().unwrap(), None), Err(RomError::Archive(_))));
+    }
+
+    #[test]
+    fn zip_entry_larger_than_the_limit() {
+        let data = zip(&[("huge.ch8", &vec![0; MAX_UNPACKED_SIZE + 1])]);
+        assert!(data.len() < 1 << 20);
+
+        assert!(matches!(unpack(data, None), Err(RomError::Archive(_))));
+    }
+
+    #[test]
+    fn zipped_cartridge_larger_than_memory() {
+        let cartridge = vec![0x47; 64 * 1024];
+        let data = zip(&[("README.txt", b"hello"), ("game.gif", &cartridge)]);
+
+        assert_eq!(unpack(data, None).unwrap(), cartridge);
+    }
+
+    #[test]
+    fn zip_with_single_rom() {
+        let data = zip(&[("README.txt", b"hello"), ("games/pong.ch8", &[0x6A, 0x02])]);
+        assert_eq!(unpack(data, None).unwrap(), [0x6A, 0x02]);
+    }
+
+    #[test]
+    fn zip_with_several_roms_needs_entry() {
+        let data = zip(&[("tetris.ch8", &[0x01]), ("pong.ch8", &[0x02])]);
+
+        let result = unpack(data.clone(), None);
+        assert!(matches!(result, Err(RomError::AmbiguousEntry(names)) if names == ["pong.ch8", "tetris.ch8"]));
+
+        assert_eq!(unpack(data.clone(), Some("tetris.ch8")).unwrap(), [0x01]);
+        assert!(matches!(unpack(data, Some("snake.ch8")), Err(RomError::EntryNotFound(_))));
+    }
+
+    #[test]
+    fn zip_entry_matches_file_name_in_directory() {
+        let data = zip(&[("roms/a.ch8", &[0x01]), ("roms/b.ch8", &[0x02])]);
+        assert_eq!(unpack(data, Some("b.ch8")).unwrap(), [0x02]);
+    }
+
+    #[test]
+    fn zip_entry_file_name_in_several_directories() {
+        let data = zip(&[("chip8/pong.ch8", &[0x01]), ("schip/pong.ch8", &[0x02])]);
+
+        let result = unpack(data.clone(), Some("pong.ch8"));
+        assert!(matches!(result, Err(RomError::AmbiguousEntry(names)) if names == ["chip8/pong.ch8", "schip/pong.ch8"]));
+        assert_eq!(unpack(data, Some("schip/pong.ch8")).unwrap(), [0x02]);
+    }
+}
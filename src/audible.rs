@@ -1,4 +1,25 @@
+use crate::tone::ToneSettings;
+
+// Sound is switched on and off at the start of emulated frame `frame` (counted by Cpu::tick), so
+// devices can play it back with exact 1/60 s timing however late the calls arrive
 pub trait Audible {
-    fn enable_sound(&mut self);
-    fn disable_sound(&mut self);
+    fn enable_sound(&mut self, frame: u64);
+    fn disable_sound(&mut self, frame: u64);
+
+    // Devices that can't change their sound (like the terminal bell) ignore this
+    fn set_tone(&mut self, _tone: ToneSettings) {}
+}
+
+impl<T: Audible + ?Sized> Audible for Box<T> {
+    fn enable_sound(&mut self, frame: u64) {
+        (**self).enable_sound(frame);
+    }
+
+    fn disable_sound(&mut self, frame: u64) {
+        (**self).disable_sound(frame);
+    }
+
+    fn set_tone(&mut self, tone: ToneSettings) {
+        (**self).set_tone(tone);
+    }
 }
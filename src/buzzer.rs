@@ -0,0 +1,183 @@
+use std::collections::VecDeque;
+
+use crate::constants::FRAMES_PER_SECOND;
+use crate::tone::Oscillator;
+
+// Buzzer switched on or off at the start of emulated frame `frame`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BuzzerEvent {
+    pub frame: u64,
+    pub on: bool,
+}
+
+// How far behind emulated time the audio plays, in frames. It has to cover the time between two
+// audio callbacks, events that arrive later than this can't be played at their exact position
+const LATENCY_FRAMES: u64 = 3;
+// Events scheduled further ahead than this many frames mean the clocks drifted apart
+const MAX_AHEAD_FRAMES: u64 = 30;
+// Room for the pending events, allocated up front because pushing happens on the audio thread.
+// The buzzer changes at most once a frame and nothing is scheduled further than MAX_AHEAD_FRAMES
+// ahead, so this is only reached when a burst of late events piles up
+const PENDING_CAPACITY: usize = 64;
+
+// Turns buzzer events stamped with emulated frames into gate changes at exact sample positions,
+// so a beep lasts exactly as many 1/60 s frames as the sound timer said, whenever the events
+// happen to arrive. Emulated time is mapped onto the audio sample clock with a fixed latency
+pub struct BuzzerSchedule {
+    sample_rate: u64,
+    // Audio samples rendered so far
+    clock: u64,
+    // Sample position of emulated frame 0, set by the first event and moved when the emulator
+    // and the audio clock drift apart (e.g. while the emulator is paused)
+    origin: Option<i64>,
+    pending: VecDeque<(u64, bool)>,
+}
+
+impl BuzzerSchedule {
+    pub fn new(sample_rate: u32) -> BuzzerSchedule {
+        BuzzerSchedule {
+            sample_rate: u64::from(sample_rate),
+            clock: 0,
+            origin: None,
+            pending: VecDeque::with_capacity(PENDING_CAPACITY),
+        }
+    }
+
+    pub fn push(&mut self, event: BuzzerEvent) {
+        let frame_position = (event.frame * self.sample_rate / u64::from(FRAMES_PER_SECOND)) as i64;
+        let latency = (LATENCY_FRAMES * self.sample_rate / u64::from(FRAMES_PER_SECOND)) as i64;
+        let max_ahead = (MAX_AHEAD_FRAMES * self.sample_rate / u64::from(FRAMES_PER_SECOND)) as i64;
+        let clock = self.clock as i64;
+
+        let origin = *self.origin.get_or_insert(clock + latency - frame_position);
+        let mut position = origin + frame_position;
+
+        // Too late to play on time or implausibly far ahead: play it after the usual latency and
+        // keep the following events relative to it
+        if position < clock || position > clock + max_ahead {
+            self.origin = Some(clock + latency - frame_position);
+            position = clock + latency;
+        }
+
+        // A full queue must not grow, the newest event replaces the last one so the buzzer still
+        // ends up in the latest state
+        if self.pending.len() == self.pending.capacity() {
+            self.pending.pop_back();
+        }
+        self.pending.push_back((position as u64, event.on));
+    }
+
+    pub fn fill(&mut self, oscillator: &mut Oscillator, out: &mut [f32]) {
+        for sample in out.iter_mut() {
+            while let Some(&(position, on)) = self.pending.front() {
+                if position > self.clock {
+                    break;
+                }
+
+                oscillator.set_gate(on);
+                self.pending.pop_front();
+            }
+
+            *sample = oscillator.next_sample();
+            self.clock += 1;
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tone::ToneSettings;
+
+    const SAMPLE_RATE: u32 = 44_100;
+    const SAMPLES_PER_FRAME: usize = 735;
+
+    fn oscillator() -> Oscillator {
+        let settings = ToneSettings { attack: 0.0, release: 0.0, ..Default::default() };
+        Oscillator::new(settings, SAMPLE_RATE)
+    }
+
+    // Indices of the first and last audible sample
+    fn audible_range(out: &[f32]) -> (usize, usize) {
+        let first = out.iter().position(|s| *s != 0.0).unwrap();
+        let last = out.iter().rposition(|s| *s != 0.0).unwrap();
+        (first, last)
+    }
+
+    #[test]
+    fn beep_lasts_exactly_its_frames() {
+        let mut schedule = BuzzerSchedule::new(SAMPLE_RATE);
+        let mut oscillator = oscillator();
+
+        schedule.push(BuzzerEvent { frame: 10, on: true });
+        schedule.push(BuzzerEvent { frame: 14, on: false });
+
+        let mut out = vec![0.0; SAMPLES_PER_FRAME * 20];
+        schedule.fill(&mut oscillator, &mut out);
+
+        let (first, last) = audible_range(&out);
+        assert_eq!(first, SAMPLES_PER_FRAME * LATENCY_FRAMES as usize);
+        assert_eq!(last + 1 - first, SAMPLES_PER_FRAME * 4);
+    }
+
+    #[test]
+    fn events_arriving_between_callbacks_keep_their_spacing() {
+        let mut schedule = BuzzerSchedule::new(SAMPLE_RATE);
+        let mut oscillator = oscillator();
+        let mut out = vec![];
+
+        let mut chunk = vec![0.0; 2048];
+        schedule.push(BuzzerEvent { frame: 0, on: true });
+        schedule.fill(&mut oscillator, &mut chunk);
+        out.extend(&chunk);
+
+        schedule.push(BuzzerEvent { frame: 2, on: false });
+        for _ in 0..2 {
+            schedule.fill(&mut oscillator, &mut chunk);
+            out.extend(&chunk);
+        }
+
+        let (first, last) = audible_range(&out);
+        assert_eq!(last + 1 - first, SAMPLES_PER_FRAME * 2);
+    }
+
+    #[test]
+    fn late_events_play_right_away() {
+        let mut schedule = BuzzerSchedule::new(SAMPLE_RATE);
+        let mut oscillator = oscillator();
+
+        schedule.push(BuzzerEvent { frame: 0, on: true });
+        schedule.push(BuzzerEvent { frame: 1, on: false });
+        let mut paused = vec![0.0; SAMPLES_PER_FRAME * 60];
+        schedule.fill(&mut oscillator, &mut paused);
+
+        // The emulator was paused for a second, frame 5 is long overdue on the audio clock
+        schedule.push(BuzzerEvent { frame: 5, on: true });
+        schedule.push(BuzzerEvent { frame: 6, on: false });
+        let mut out = vec![0.0; SAMPLES_PER_FRAME * 10];
+        schedule.fill(&mut oscillator, &mut out);
+
+        let (first, last) = audible_range(&out);
+        assert_eq!(first, SAMPLES_PER_FRAME * LATENCY_FRAMES as usize);
+        assert_eq!(last + 1 - first, SAMPLES_PER_FRAME);
+    }
+
+    #[test]
+    fn pending_events_never_grow_the_queue() {
+        let mut schedule = BuzzerSchedule::new(SAMPLE_RATE);
+        let mut oscillator = oscillator();
+        let capacity = schedule.pending.capacity();
+
+        // A stalled audio callback: a thousand late frames arrive at once
+        for frame in 0..1000 {
+            schedule.push(BuzzerEvent { frame: frame * 100, on: frame % 2 == 0 });
+        }
+        assert_eq!(schedule.pending.capacity(), capacity);
+
+        // The last event still wins
+        let mut out = vec![0.0; SAMPLES_PER_FRAME * 10];
+        schedule.fill(&mut oscillator, &mut out);
+        assert!(out[SAMPLES_PER_FRAME * 5..].iter().all(|s| *s == 0.0));
+    }
+}
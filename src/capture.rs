@@ -0,0 +1,293 @@
+use std::borrow::Cow;
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+use crate::constants::{FRAMES_PER_SECOND, SCREEN_WIDTH, SCREEN_HEIGHT};
+use crate::options::Options;
+use crate::palette::Palette;
+
+// Every CHIP-8 pixel becomes a square of this many image pixels
+pub const DEFAULT_SCALE: usize = 8;
+pub const MAX_SCALE: usize = 32;
+
+#[derive(Debug)]
+pub enum CaptureError {
+    Io(io::Error),
+    Png(png::EncodingError),
+    Gif(gif::EncodingError),
+}
+
+impl fmt::Display for CaptureError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CaptureError::Io(e) => write!(f, "{e}"),
+            CaptureError::Png(e) => write!(f, "PNG encoding failed: {e}"),
+            CaptureError::Gif(e) => write!(f, "GIF encoding failed: {e}"),
+        }
+    }
+}
+
+impl From<io::Error> for CaptureError {
+    fn from(e: io::Error) -> Self {
+        CaptureError::Io(e)
+    }
+}
+
+impl From<png::EncodingError> for CaptureError {
+    fn from(e: png::EncodingError) -> Self {
+        CaptureError::Png(e)
+    }
+}
+
+impl From<gif::EncodingError> for CaptureError {
+    fn from(e: gif::EncodingError) -> Self {
+        CaptureError::Gif(e)
+    }
+}
+
+// Writes a framebuffer (plane bits per pixel, as kept by every screen) as an indexed PNG in the
+// colours of `palette`
+pub fn write_png<W: Write>(writer: W, fb: &[u8], palette: &Palette, scale: usize) -> Result<(), CaptureError> {
+    let mut encoder = png::Encoder::new(writer, (SCREEN_WIDTH * scale) as u32, (SCREEN_HEIGHT * scale) as u32);
+    encoder.set_color(png::ColorType::Indexed);
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder.set_palette(rgb(palette));
+
+    let mut writer = encoder.write_header()?;
+    writer.write_image_data(&scaled_indices(fb, scale))?;
+    writer.finish()?;
+
+    Ok(())
+}
+
+pub fn save_png<P: AsRef<Path>>(path: P, fb: &[u8], palette: &Palette, scale: usize) -> Result<(), CaptureError> {
+    write_png(BufWriter::new(File::create(path)?), fb, palette, scale)
+}
+
+// Writes one frame per call to an endlessly looping animated GIF. A frame is only stored once
+// the screen changes, with a delay covering all the 60 Hz frames it stayed on screen
+pub struct GifRecorder<W: Write> {
+    encoder: gif::Encoder<W>,
+    palette: Palette,
+    scale: usize,
+    // Frames handed in so far
+    frames: u64,
+    // The picture on screen, its palette and the frame it appeared at
+    pending: Option<(Vec<u8>, Palette, u64)>,
+}
+
+impl GifRecorder<BufWriter<File>> {
+    pub fn create<P: AsRef<Path>>(path: P, palette: &Palette, scale: usize) -> Result<Self, CaptureError> {
+        GifRecorder::new(BufWriter::new(File::create(path)?), palette, scale)
+    }
+}
+
+impl<W: Write> GifRecorder<W> {
+    pub fn new(writer: W, palette: &Palette, scale: usize) -> Result<Self, CaptureError> {
+        let mut encoder = gif::Encoder::new(writer, (SCREEN_WIDTH * scale) as u16, (SCREEN_HEIGHT * scale) as u16, &rgb(palette))?;
+        encoder.set_repeat(gif::Repeat::Infinite)?;
+
+        Ok(GifRecorder {
+            encoder,
+            palette: *palette,
+            scale,
+            frames: 0,
+            pending: None,
+        })
+    }
+
+    pub fn add_frame(&mut self, fb: &[u8], palette: &Palette) -> Result<(), CaptureError> {
+        let unchanged = matches!(&self.pending, Some((pending, pending_palette, _)) if pending == fb && pending_palette == palette);
+        if !unchanged {
+            self.write_pending()?;
+            self.pending = Some((fb.to_vec(), *palette, self.frames));
+        }
+        self.frames += 1;
+
+        Ok(())
+    }
+
+    pub fn finish(mut self) -> Result<W, CaptureError> {
+        self.write_pending()?;
+
+        Ok(self.encoder.into_inner()?)
+    }
+
+    fn write_pending(&mut self) -> Result<(), CaptureError> {
+        let Some((fb, palette, start)) = self.pending.take() else {
+            return Ok(());
+        };
+
+        // GIF delays are in 1/100 s, rounding the start and end separately keeps the total in sync
+        let centiseconds = |frame: u64| (frame * 100 + u64::from(FRAMES_PER_SECOND) / 2) / u64::from(FRAMES_PER_SECOND);
+        let delay = (centiseconds(self.frames) - centiseconds(start)).clamp(1, u64::from(u16::MAX)) as u16;
+
+        let frame = gif::Frame {
+            width: (SCREEN_WIDTH * self.scale) as u16,
+            height: (SCREEN_HEIGHT * self.scale) as u16,
+            buffer: Cow::Owned(scaled_indices(&fb, self.scale)),
+            // Only frames shown after a palette switch need their own colour table
+            palette: (palette != self.palette).then(|| rgb(&palette)),
+            delay,
+            ..Default::default()
+        };
+        self.encoder.write_frame(&frame)?;
+
+        Ok(())
+    }
+}
+
+// The captures a frontend makes: numbered screenshots and GIF recordings on demand, plus the
+// --screenshot and --record-gif files asked for on the command line
+pub struct Capture {
+    scale: usize,
+    // Written when the emulator quits
+    exit_screenshot: Option<String>,
+    recording: Option<GifRecorder<BufWriter<File>>>,
+}
+
+impl Capture {
+    pub fn new(options: &Options, palette: &Palette) -> Result<Capture, CaptureError> {
+        let recording = match &options.record_gif {
+            Some(path) => Some(GifRecorder::create(path, palette, options.capture_scale)?),
+            None => None,
+        };
+
+        Ok(Capture {
+            scale: options.capture_scale,
+            exit_screenshot: options.screenshot.clone(),
+            recording,
+        })
+    }
+
+    pub fn screenshot(&self, fb: &[u8], palette: &Palette) -> Result<PathBuf, CaptureError> {
+        let path = numbered_path("chip8-screenshot", "png");
+        save_png(&path, fb, palette, self.scale)?;
+
+        Ok(path)
+    }
+
+    pub fn is_recording(&self) -> bool {
+        self.recording.is_some()
+    }
+
+    pub fn start_recording(&mut self, palette: &Palette) -> Result<PathBuf, CaptureError> {
+        let path = numbered_path("chip8-recording", "gif");
+        self.recording = Some(GifRecorder::create(&path, palette, self.scale)?);
+
+        Ok(path)
+    }
+
+    pub fn stop_recording(&mut self) -> Result<(), CaptureError> {
+        match self.recording.take() {
+            Some(recording) => recording.finish()?.flush().map_err(CaptureError::from),
+            None => Ok(()),
+        }
+    }
+
+    // Call once per emulated frame, a failed recording is stopped
+    pub fn add_frame(&mut self, fb: &[u8], palette: &Palette) -> Result<(), CaptureError> {
+        let Some(recording) = self.recording.as_mut() else {
+            return Ok(());
+        };
+
+        let result = recording.add_frame(fb, palette);
+        if result.is_err() {
+            self.recording = None;
+        }
+
+        result
+    }
+
+    // Completes the recording and writes the --screenshot file
+    pub fn finish(mut self, fb: &[u8], palette: &Palette) -> Result<(), CaptureError> {
+        self.stop_recording()?;
+
+        match &self.exit_screenshot {
+            Some(path) => save_png(path, fb, palette, self.scale),
+            None => Ok(()),
+        }
+    }
+}
+
+// Picks the first of `stem`-1.`extension`, `stem`-2.`extension`, ... that doesn't exist yet
+pub fn numbered_path(stem: &str, extension: &str) -> PathBuf {
+    (1..)
+        .map(|n| PathBuf::from(format!("{stem}-{n}.{extension}")))
+        .find(|path| !path.exists())
+        .unwrap()
+}
+
+fn rgb(palette: &Palette) -> Vec<u8> {
+    palette.colors.iter().flat_map(|color| color.to_be_bytes()[1..].to_vec()).collect()
+}
+
+fn scaled_indices(fb: &[u8], scale: usize) -> Vec<u8> {
+    let mut indices = Vec::with_capacity(fb.len() * scale * scale);
+
+    for row in fb.chunks(SCREEN_WIDTH) {
+        let line: Vec<u8> = row.iter().flat_map(|planes| std::iter::repeat(planes & 0x3).take(scale)).collect();
+        for _ in 0..scale {
+            indices.extend_from_slice(&line);
+        }
+    }
+
+    indices
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::palette::{AMBER, CLASSIC_GREEN};
+
+    fn checkerboard() -> Vec<u8> {
+        (0..SCREEN_WIDTH * SCREEN_HEIGHT).map(|i| ((i % SCREEN_WIDTH + i / SCREEN_WIDTH) % 2) as u8).collect()
+    }
+
+    #[test]
+    fn png_is_scaled_in_palette_colours() {
+        let mut data = vec![];
+        write_png(&mut data, &checkerboard(), &AMBER, 2).unwrap();
+
+        let decoder = png::Decoder::new(data.as_slice());
+        let mut reader = decoder.read_info().unwrap();
+        let mut image = vec![0; reader.output_buffer_size()];
+        reader.next_frame(&mut image).unwrap();
+
+        let info = reader.info();
+        assert_eq!((info.width, info.height), (SCREEN_WIDTH as u32 * 2, SCREEN_HEIGHT as u32 * 2));
+        assert_eq!(info.palette.as_deref().unwrap()[..6], [0x1a, 0x0f, 0x00, 0xff, 0xb0, 0x00]);
+        // Pixel (0, 0) is background and covers 2x2 image pixels, pixel (1, 0) is lit
+        let width = SCREEN_WIDTH * 2;
+        assert_eq!(&image[..4], &[0, 0, 1, 1]);
+        assert_eq!(&image[width..width + 4], &[0, 0, 1, 1]);
+    }
+
+    #[test]
+    fn gif_keeps_unchanged_frames_on_screen() {
+        let blank = vec![0; SCREEN_WIDTH * SCREEN_HEIGHT];
+        let mut recorder = GifRecorder::new(vec![], &CLASSIC_GREEN, 1).unwrap();
+        for _ in 0..60 {
+            recorder.add_frame(&blank, &CLASSIC_GREEN).unwrap();
+        }
+        for _ in 0..3 {
+            recorder.add_frame(&checkerboard(), &AMBER).unwrap();
+        }
+        let data = recorder.finish().unwrap();
+
+        let mut options = gif::DecodeOptions::new();
+        options.set_color_output(gif::ColorOutput::Indexed);
+        let mut decoder = options.read_info(data.as_slice()).unwrap();
+
+        let first = decoder.read_next_frame().unwrap().unwrap();
+        assert_eq!(first.delay, 100);
+        assert!(first.palette.is_none());
+        let second = decoder.read_next_frame().unwrap().unwrap();
+        assert_eq!(second.delay, 5);
+        assert!(second.palette.is_some());
+        assert!(decoder.read_next_frame().unwrap().is_none());
+    }
+}
@@ -0,0 +1,192 @@
+use serde_json::Value;
+
+use crate::octo::assemble;
+use crate::palette::Palette;
+use crate::quirks::Quirks;
+use crate::rom::RomError;
+
+// Octo's defaults for options a cartridge leaves out
+const OCTO_TICKRATE: usize = 20;
+const OCTO_BACKGROUND: u32 = 0x00996600;
+const OCTO_FILL: u32 = 0x00FFCC00;
+const OCTO_FILL_2: u32 = 0x00FF6600;
+const OCTO_BLEND: u32 = 0x00662200;
+
+// Emulator settings stored in a cartridge next to the program
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CartridgeOptions {
+    pub instructions_per_frame: usize,
+    pub palette: Palette,
+    pub quirks: Quirks,
+}
+
+#[derive(Debug)]
+pub struct Cartridge {
+    pub program: Vec<u8>,
+    pub options: CartridgeOptions,
+}
+
+pub fn is_cartridge(data: &[u8]) -> bool {
+    data.starts_with(b"GIF87a") || data.starts_with(b"GIF89a")
+}
+
+impl Cartridge {
+    // Octo cartridges are GIFs with a JSON document hidden in the two low bits of every pixel's
+    // palette index, four pixels per byte across all frames. The document starts with its length
+    // as a 32 bit big endian number and holds the options and the program's Octo source.
+    // Labels are assembled to addresses relative to `load_address`
+    pub fn parse(data: &[u8], load_address: u16) -> Result<Cartridge, RomError> {
+        let payload = decode_payload(data)?;
+        let json: Value = serde_json::from_slice(&payload).map_err(|e| RomError::Cartridge(e.to_string()))?;
+
+        let source = json["program"].as_str().ok_or_else(|| RomError::Cartridge("no program".to_string()))?;
+        let options = &json["options"];
+
+        Ok(Cartridge {
+            program: assemble(source, load_address)?,
+            options: CartridgeOptions {
+                instructions_per_frame: options["tickrate"].as_u64().map_or(OCTO_TICKRATE, |t| t.max(1) as usize),
+                palette: Palette {
+                    name: "cartridge",
+                    colors: [
+                        color(&options["backgroundColor"], OCTO_BACKGROUND),
+                        color(&options["fillColor"], OCTO_FILL),
+                        color(&options["fillColor2"], OCTO_FILL_2),
+                        color(&options["blendColor"], OCTO_BLEND),
+                    ],
+                    grid: color(&options["blendColor"], OCTO_BLEND),
+                },
+                quirks: Quirks {
+                    shift: flag(&options["shiftQuirks"]),
+                    load_store: flag(&options["loadStoreQuirks"]),
+                    vf_order: flag(&options["vfOrderQuirks"]),
+                    clip: flag(&options["clipQuirks"]),
+                    jump: flag(&options["jumpQuirks"]),
+                    logic: flag(&options["logicQuirks"]),
+                    vblank: flag(&options["vBlankQuirks"]),
+                },
+            },
+        })
+    }
+}
+
+fn decode_payload(data: &[u8]) -> Result<Vec<u8>, RomError> {
+    let mut options = gif::DecodeOptions::new();
+    options.set_color_output(gif::ColorOutput::Indexed);
+    let mut decoder = options.read_info(data).map_err(|e| RomError::Cartridge(e.to_string()))?;
+
+    let mut bytes = vec![];
+    while let Some(frame) = decoder.read_next_frame().map_err(|e| RomError::Cartridge(e.to_string()))? {
+        bytes.extend(frame.buffer.chunks_exact(4).map(|pixels| {
+            pixels.iter().fold(0, |byte, pixel| byte << 2 | (pixel & 0x3))
+        }));
+    }
+
+    if bytes.len() < 4 {
+        return Err(RomError::Cartridge("image too small to hold a program".to_string()));
+    }
+
+    let size = u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as usize;
+    bytes.get(4..4 + size)
+        .map(<[u8]>::to_vec)
+        .ok_or_else(|| RomError::Cartridge(format!("payload of {size} bytes doesn't fit in the image")))
+}
+
+fn flag(value: &Value) -> bool {
+    value.as_bool().unwrap_or(false)
+}
+
+// Octo stores colours as CSS style "#RRGGBB" strings
+fn color(value: &Value, default: u32) -> u32 {
+    value.as_str()
+        .and_then(|s| s.strip_prefix('#'))
+        .filter(|hex| hex.len() == 6)
+        .and_then(|hex| u32::from_str_radix(hex, 16).ok())
+        .unwrap_or(default)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Builds a single frame cartridge the way Octo lays out the payload
+    fn cartridge(json: &str) -> Vec<u8> {
+        let mut payload = (json.len() as u32).to_be_bytes().to_vec();
+        payload.extend(json.as_bytes());
+
+        let mut pixels: Vec<u8> = payload.iter()
+            .flat_map(|byte| [byte >> 6, byte >> 4 & 0x3, byte >> 2 & 0x3, byte & 0x3])
+            .collect();
+        let width = 64;
+        let height = pixels.len().div_ceil(width);
+        pixels.resize(width * height, 0);
+
+        let palette = [0, 0, 0, 85, 85, 85, 170, 170, 170, 255, 255, 255];
+        let mut data = vec![];
+        {
+            let mut encoder = gif::Encoder::new(&mut data, width as u16, height as u16, &palette).unwrap();
+            let frame = gif::Frame {
+                width: width as u16,
+                height: height as u16,
+                buffer: pixels.into(),
+                ..Default::default()
+            };
+            encoder.write_frame(&frame).unwrap();
+        }
+
+        data
+    }
+
+    #[test]
+    fn parse_reads_program_and_options() {
+        let data = cartridge(r##"{
+            "program": ": main 0x00 0xE0 # cls\n 0b00010010 2 ",
+            "options": { "tickrate": 200, "fillColor": "#FF0000", "clipQuirks": true, "shiftQuirks": true }
+        }"##);
+        assert!(is_cartridge(&data));
+
+        let cartridge = Cartridge::parse(&data, 0x200).unwrap();
+        assert_eq!(cartridge.program, [0x00, 0xE0, 0x12, 0x02]);
+        assert_eq!(cartridge.options.instructions_per_frame, 200);
+        assert_eq!(cartridge.options.palette.colors, [OCTO_BACKGROUND, 0x00FF0000, OCTO_FILL_2, OCTO_BLEND]);
+        assert!(cartridge.options.quirks.clip && cartridge.options.quirks.shift);
+        assert!(!cartridge.options.quirks.load_store && !cartridge.options.quirks.vblank);
+    }
+
+    #[test]
+    fn jump_to_main_when_it_is_not_first() {
+        let data = cartridge(r#"{ "program": ": sprite 0xFF 0x81 : main 0x12 0x06", "options": {} }"#);
+
+        let cartridge = Cartridge::parse(&data, 0x200).unwrap();
+        assert_eq!(cartridge.program, [0x12, 0x04, 0xFF, 0x81, 0x12, 0x06]);
+        assert_eq!(cartridge.options.instructions_per_frame, OCTO_TICKRATE);
+
+        let cartridge = Cartridge::parse(&data, 0x600).unwrap();
+        assert_eq!(cartridge.program[..2], [0x16, 0x04]);
+    }
+
+    #[test]
+    fn octo_instructions_are_assembled() {
+        let data = cartridge(r#"{ "program": ": main\n  v0 := 5\n  loop again", "options": {} }"#);
+        assert_eq!(Cartridge::parse(&data, 0x200).unwrap().program, [0x60, 0x05, 0x12, 0x02]);
+    }
+
+    #[test]
+    fn super_chip_instructions_are_not_supported() {
+        let data = cartridge(r#"{ "program": ": main\n  hires\n  loop again", "options": {} }"#);
+        assert!(matches!(Cartridge::parse(&data, 0x200), Err(RomError::UnsupportedOctoSource(token)) if token == "hires"));
+    }
+
+    #[test]
+    fn program_needs_main() {
+        let data = cartridge(r#"{ "program": "0x00 0xE0", "options": {} }"#);
+        assert!(matches!(Cartridge::parse(&data, 0x200), Err(RomError::Cartridge(_))));
+    }
+
+    #[test]
+    fn broken_image() {
+        let data = cartridge(r#"{ "program": ": main 0", "options": {} }"#);
+        assert!(matches!(Cartridge::parse(&data[..20], 0x200), Err(RomError::Cartridge(_))));
+    }
+}
@@ -1,3 +1,6 @@
+// Bytes of RAM, addresses are 12 bits
+pub const MEMORY_SIZE: usize = 4096;
 pub const SCREEN_WIDTH: usize = 64;
 pub const SCREEN_HEIGHT: usize = 32;
-pub const PIXEL_COLOR: u32 = 0x0000ff00;
+pub const FRAMES_PER_SECOND: u32 = 60;
+pub const INSTRUCTIONS_PER_FRAME: usize = 8;
@@ -1,14 +1,20 @@
 use crate::audible::Audible;
-use crate::constants::{SCREEN_WIDTH, SCREEN_HEIGHT};
 use crate::drawable::Drawable;
+use crate::font::{FontAddressError, FontSet, DEFAULT_FONT_ADDRESS, FONT_SIZE, GLYPH_SIZE};
+use crate::rom::Rom;
+use crate::quirks::Quirks;
+use crate::constants::{MEMORY_SIZE, SCREEN_WIDTH, SCREEN_HEIGHT, INSTRUCTIONS_PER_FRAME};
 use crate::instructions::Instructions;
 use crate::opcode_decoders::{OPCODE_DECODERS};
+use crate::tracer::{TraceEntry, Tracer};
+use std::error::Error;
 use std::fmt;
+use std::ops::Range;
 use std::num::Wrapping;
 use rand::Rng;
 
 pub struct Cpu {
-    pub ram: [u8; 4096],
+    pub ram: [u8; MEMORY_SIZE],
     pub registers: [u8; 16],
     pub stack: [u16; 16],
     pub st: u8,
@@ -16,8 +22,46 @@ pub struct Cpu {
     pub i: u16,
     pub sp: u8,
     pub pc: u16,
+    font_address: u16,
+    pub quirks: Quirks,
+    pub instructions_per_frame: usize,
+    // Set by DXYN with the vblank quirk, the rest of the frame is skipped until the next tick
+    waiting_for_vblank: bool,
+    // Kept to put the program and font back in memory on reset
+    rom: Option<Rom>,
+    font: Option<FontSet>,
+    // Emulated frames since power on, timestamps the buzzer events
+    frame: u64,
+    // Instructions executed since power on
+    cycle: u64,
+    tracer: Option<Tracer>,
 }
 
+// Why the program can't go on, `pc` is the address of the failing instruction
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CpuError {
+    PcOutOfBounds { pc: u16 },
+    UnsupportedOpcode { pc: u16, opcode: u16 },
+    StackOverflow { pc: u16 },
+    StackUnderflow { pc: u16 },
+    // An instruction reading or writing memory from I ran past the end of RAM
+    MemoryOutOfBounds { pc: u16, i: u16 },
+}
+
+impl fmt::Display for CpuError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CpuError::PcOutOfBounds { pc } => write!(f, "PC {pc:#05X} is outside of memory"),
+            CpuError::UnsupportedOpcode { pc, opcode } => write!(f, "Unsupported opcode {opcode:04X} at {pc:#05X}"),
+            CpuError::StackOverflow { pc } => write!(f, "Stack overflow, more than 16 nested calls at {pc:#05X}"),
+            CpuError::StackUnderflow { pc } => write!(f, "Return with an empty stack at {pc:#05X}"),
+            CpuError::MemoryOutOfBounds { pc, i } => write!(f, "Memory access from I = {i:#05X} runs past the end of memory at {pc:#05X}"),
+        }
+    }
+}
+
+impl Error for CpuError {}
+
 impl fmt::Debug for Cpu {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         writeln!(f, "Registers:").unwrap();
@@ -27,7 +71,7 @@ impl fmt::Debug for Cpu {
         writeln!(f, "DT: {:#X} ST: {:#X}", self.dt, self.st).unwrap();
         writeln!(f, "I: {:#X}", self.i).unwrap();
 
-        if self.sp < 17 {
+        if self.sp < 16 {
             writeln!(f, "Last stack addr: {:#X}", self.stack[self.sp as usize]).unwrap();
         }
 
@@ -39,12 +83,20 @@ impl fmt::Debug for Cpu {
 struct Instruction {
     pub int: Instructions,
     pub args: Vec<u8>,
+    pub opcode: u16,
+    pub mnemonic: &'static str,
+}
+
+impl Default for Cpu {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Cpu {
     pub fn new() -> Cpu {
         Cpu{
-            ram: [0; 4096],
+            ram: [0; MEMORY_SIZE],
             registers: [0; 16],
             stack: [0; 16],
             st: 0x0,
@@ -52,14 +104,77 @@ impl Cpu {
             i: 0x0,
             sp: 0xFF,
             pc: 0x200,
+            font_address: DEFAULT_FONT_ADDRESS,
+            quirks: Quirks::default(),
+            instructions_per_frame: INSTRUCTIONS_PER_FRAME,
+            waiting_for_vblank: false,
+            rom: None,
+            font: None,
+            frame: 0,
+            cycle: 0,
+            tracer: None,
+        }
+    }
+
+    // Copies the program to its load address and starts executing it from a clean state. Memory
+    // below the load address and the font are left alone, everything else after it is cleared.
+    // A program that would overlap the font is rejected and nothing is changed
+    pub fn load(&mut self, rom: &Rom) -> Result<(), FontAddressError> {
+        check_font_overlap(self.font_address, rom)?;
+
+        (self.quirks, self.instructions_per_frame) = match rom.cartridge_options() {
+            Some(options) => (options.quirks, options.instructions_per_frame),
+            None => (Quirks::default(), INSTRUCTIONS_PER_FRAME),
+        };
+        self.rom = Some(rom.clone());
+
+        self.restart();
+
+        Ok(())
+    }
+
+    pub fn rom(&self) -> Option<&Rom> {
+        self.rom.as_ref()
+    }
+
+    // Writes the font to font_address, LD F, Vx points into it
+    pub fn load_font(&mut self, font: FontSet) {
+        self.font = Some(font);
+        self.write_font();
+    }
+
+    pub fn font_address(&self) -> u16 {
+        self.font_address
+    }
+
+    // Moves the font, the glyphs at the old address are cleared. It has to fit in memory and
+    // stay clear of the loaded program, programs loaded later are checked against it by load
+    pub fn set_font_address(&mut self, address: u16) -> Result<(), FontAddressError> {
+        if address as usize + FONT_SIZE as usize > MEMORY_SIZE {
+            return Err(FontAddressError::OutsideMemory(address));
         }
+        if let Some(rom) = &self.rom {
+            check_font_overlap(address, rom)?;
+        }
+
+        let old = self.font_range();
+        self.ram[old].fill(0);
+        self.font_address = address;
+        self.write_font();
+
+        Ok(())
     }
 
-    pub fn load_rom(&mut self, rom: [u8; 4096]) {
-        self.ram = rom;
+    pub fn frame(&self) -> u64 {
+        self.frame
     }
 
-    pub fn tick(&mut self, audio_device: &mut Box<dyn Audible>) {
+    // Starts the next 60 Hz frame: counts down the timers and switches the buzzer off when the
+    // sound timer runs out
+    pub fn tick(&mut self, audio_device: &mut dyn Audible) {
+        self.frame += 1;
+        self.waiting_for_vblank = false;
+
         if self.dt > 0 {
             self.dt -= 1;
         }
@@ -68,36 +183,135 @@ impl Cpu {
             self.st -= 1;
   
             if self.st == 0 {
-                audio_device.disable_sound();
+                audio_device.disable_sound(self.frame);
             }
         }
     }
 
-    pub fn reset(&mut self) {
+    // Restarts the loaded program from a fresh copy of it and the font, with a blank screen and
+    // the buzzer off. Memory outside the program and font is left as it is
+    pub fn soft_reset(&mut self, screen: &mut dyn Drawable, audio_device: &mut dyn Audible) {
+        self.restart();
+        // The program may have written over the font
+        self.write_font();
+
+        screen.cls();
+        audio_device.disable_sound(self.frame);
+    }
+
+    // Like switching the machine off and on again: all of memory is cleared before the font
+    // and program are loaded again
+    pub fn hard_reset(&mut self, screen: &mut dyn Drawable, audio_device: &mut dyn Audible) {
         self.ram.fill(0x0);
+        self.soft_reset(screen, audio_device);
+    }
+
+    fn write_font(&mut self) {
+        let Some(font) = self.font else {
+            return;
+        };
+
+        let range = self.font_range();
+        self.ram[range].copy_from_slice(&font.bytes());
+    }
+
+    // Where the font is in memory, empty when none was loaded
+    fn font_range(&self) -> Range<usize> {
+        let start = self.font_address as usize;
+        match self.font {
+            Some(_) => start..start + FONT_SIZE as usize,
+            None => start..start,
+        }
+    }
+
+    // Puts a fresh copy of the program in memory, undoing anything it wrote there, and clears
+    // registers, timers and the stack
+    fn restart(&mut self) {
         self.registers.fill(0x0);
+        self.stack.fill(0x0);
         self.st = 0x0;
         self.dt = 0x0;
         self.i = 0x0;
-        self.pc = 0x200;
         self.sp = 0xff;
+        self.waiting_for_vblank = false;
+
+        match &self.rom {
+            Some(rom) => {
+                let start = rom.load_address() as usize;
+                let font = self.font_range();
+
+                for (address, byte) in self.ram.iter_mut().enumerate().skip(start) {
+                    if !font.contains(&address) {
+                        *byte = 0;
+                    }
+                }
+                self.ram[start..start + rom.data().len()].copy_from_slice(rom.data());
+                self.pc = rom.load_address();
+            },
+            None => self.pc = 0x200,
+        }
+    }
+
+    pub fn waiting_for_vblank(&self) -> bool {
+        self.waiting_for_vblank
+    }
+
+    // Logs every instruction from now on, None stops tracing
+    pub fn set_tracer(&mut self, tracer: Option<Tracer>) {
+        self.tracer = tracer;
+    }
+
+    pub fn step(&mut self, screen: &mut dyn Drawable, audio_device: &mut dyn Audible, pressed_keys: &[u8; 16], last_key: &mut u8) -> Result<(), CpuError> {
+        let result = self.fetch()
+            .and_then(|opcode| self.decode(opcode))
+            .and_then(|instruction| {
+                self.trace(&instruction);
+                self.execute(instruction, screen, audio_device, pressed_keys, last_key)
+            });
+        self.cycle += 1;
+
+        if let (Err(error), Some(tracer)) = (&result, self.tracer.as_mut()) {
+            tracer.report(error);
+        }
+
+        result
     }
 
-    pub fn step(&mut self, screen: &mut Box<dyn Drawable>, audio_device: &mut Box<dyn Audible>, pressed_keys: &[u8; 16], last_key: &mut u8) {
-        let opcode = self.fetch();
-        let instruction = self.decode(opcode);
+    fn fetch(&self) -> Result<u16, CpuError> {
+        let pc = self.pc as usize;
+        if pc + 1 >= self.ram.len() {
+            return Err(CpuError::PcOutOfBounds { pc: self.pc });
+        }
+
+        Ok(u16::from(self.ram[pc]) << 8 | u16::from(self.ram[pc + 1]))
+    }
 
-        self.execute(instruction, screen, audio_device, pressed_keys, last_key);
+    fn trace(&mut self, instruction: &Instruction) {
+        if let Some(tracer) = self.tracer.as_mut() {
+            tracer.record(TraceEntry {
+                cycle: self.cycle,
+                pc: self.pc,
+                opcode: instruction.opcode,
+                instruction: instruction.int,
+                mnemonic: instruction.mnemonic,
+                registers: self.registers,
+                i: self.i,
+                sp: self.sp,
+            });
+        }
     }
 
-    fn fetch(&self) -> u16 {
-        match self.pc {
-            0..=4096 => u16::from(self.ram[self.pc as usize]) << 8 | u16::from(self.ram[self.pc as usize + 1]),
-            _ => panic!("PC address out of ram bounds"),
+    // The `len` bytes of memory starting at I
+    fn memory_at_i(&self, len: usize) -> Result<Range<usize>, CpuError> {
+        let start = self.i as usize;
+        if start + len > self.ram.len() {
+            return Err(CpuError::MemoryOutOfBounds { pc: self.pc, i: self.i });
         }
+
+        Ok(start..start + len)
     }
 
-    fn decode(&self, opcode: u16) -> Instruction {
+    fn decode(&self, opcode: u16) -> Result<Instruction, CpuError> {
         for (_, decoder) in OPCODE_DECODERS.iter() {
             let masking_result = opcode & decoder.mask;
             if masking_result == decoder.pattern {
@@ -107,17 +321,19 @@ impl Cpu {
                     arg
                 }).collect();
 
-                return Instruction{
+                return Ok(Instruction{
                     int: decoder.instruction,
                     args,
-                }
+                    opcode,
+                    mnemonic: decoder.name,
+                })
             }
         }
 
-        panic!("Unknown instruction");
+        Err(CpuError::UnsupportedOpcode { pc: self.pc, opcode })
     }
 
-    fn execute(&mut self, instr: Instruction, screen: &mut Box<dyn Drawable>, audio_device: &mut Box<dyn Audible>, pressed_keys: &[u8; 16], last_key: &mut u8) {
+    fn execute(&mut self, instr: Instruction, screen: &mut dyn Drawable, audio_device: &mut dyn Audible, pressed_keys: &[u8; 16], last_key: &mut u8) -> Result<(), CpuError> {
         match instr.int {
             Instructions::Cls => {
                 screen.cls();
@@ -125,27 +341,26 @@ impl Cpu {
             },
             Instructions::Ret => {
                 if self.sp > 0xf {
-                    panic!("Trying to return with empty stack");
+                    return Err(CpuError::StackUnderflow { pc: self.pc });
                 }
 
                 self.pc = self.stack[self.sp as usize];
-                self.sp -= 1;
+                self.sp = self.sp.wrapping_sub(1); // Back to the empty marker after the last return
             },
             Instructions::Jp => {
                 self.pc = u16::from(instr.args[0]) << 8 | u16::from(instr.args[1]);
             },
             Instructions::Call => {
+                if self.sp == 0xf {
+                    return Err(CpuError::StackOverflow { pc: self.pc });
+                }
+
                 if self.sp > 0xf { // Using value larger than stack size to indicate that stack is empty
-                    self.sp = 1;
+                    self.sp = 0;
                 } else {
                     self.sp += 1;
                 }
 
-                if self.sp > 0xf {
-                    panic!("stack pointer out of bounds");
-                }
-
-
                 self.stack[self.sp as usize] = self.pc + 2;
                 self.pc = u16::from(instr.args[0]) << 8 | u16::from(instr.args[1]);
             },
@@ -186,63 +401,63 @@ impl Cpu {
             },
             Instructions::OrVxVy => {
                 self.registers[instr.args[0] as usize] |= self.registers[instr.args[1] as usize];
+                if self.quirks.logic {
+                    self.registers[0xf] = 0;
+                }
                 self.pc += 2;
             },
             Instructions::AndVxVy => {
                 self.registers[instr.args[0] as usize] &= self.registers[instr.args[1] as usize];
+                if self.quirks.logic {
+                    self.registers[0xf] = 0;
+                }
                 self.pc += 2;
             },
             Instructions::XorVxVy => {
                 self.registers[instr.args[0] as usize] ^= self.registers[instr.args[1] as usize];
+                if self.quirks.logic {
+                    self.registers[0xf] = 0;
+                }
                 self.pc += 2;
             },
             Instructions::AddVxVy => {
-                let result = u16::from(self.registers[instr.args[0] as usize]) + u16::from(self.registers[instr.args[1] as usize]);
-                if result > 255 {
-                    self.registers[0xf] = 0x1;
-                } else {
-                    self.registers[0xf] = 0x0;
-                }
+                // Result and flag come from the original operands, see set_with_flag for which
+                // of them ends up in VF when it is Vx
+                let vx = self.registers[instr.args[0] as usize];
+                let vy = self.registers[instr.args[1] as usize];
+                let (result, carry) = vx.overflowing_add(vy);
 
-                self.registers[instr.args[0] as usize] = result as u8;
+                self.set_with_flag(instr.args[0], result, u8::from(carry));
 
                 self.pc += 2;
             },
             Instructions::SubVxVy => {
-                self.registers[0xf] = 0;
+                let vx = self.registers[instr.args[0] as usize];
+                let vy = self.registers[instr.args[1] as usize];
 
-                if self.registers[instr.args[0] as usize] > self.registers[instr.args[1] as usize] {
-                    self.registers[0xf] = 1;
-                }
-
-                let result = Wrapping(self.registers[instr.args[0] as usize]) - Wrapping(self.registers[instr.args[1] as usize]);
-                self.registers[instr.args[0] as usize] = result.0;
+                let result = Wrapping(vx) - Wrapping(vy);
+                self.set_with_flag(instr.args[0], result.0, u8::from(vx >= vy)); // NOT borrow
 
                 self.pc += 2;
             },
             Instructions::ShrVxVy => {
-                let lsb_vy = self.registers[instr.args[0] as usize] & 1;
-                self.registers[0xf] = lsb_vy;
-                self.registers[instr.args[0] as usize] >>= 1;
+                let value = self.shift_operand(&instr);
+                self.set_with_flag(instr.args[0], value >> 1, value & 1);
 
                 self.pc += 2;
             },
             Instructions::SubnVxVy => {
-                self.registers[0xf] = 0;
-
-                if self.registers[instr.args[1] as usize] > self.registers[instr.args[0] as usize] {
-                    self.registers[0xf] = 1;
-                }
+                let vx = self.registers[instr.args[0] as usize];
+                let vy = self.registers[instr.args[1] as usize];
 
-                let result = Wrapping(self.registers[instr.args[1] as usize]) - Wrapping(self.registers[instr.args[0] as usize]);
-                self.registers[instr.args[0] as usize] = result.0;
+                let result = Wrapping(vy) - Wrapping(vx);
+                self.set_with_flag(instr.args[0], result.0, u8::from(vy >= vx)); // NOT borrow
 
                 self.pc += 2;
             },
             Instructions::ShlVxVy => {
-                let msb_vy = self.registers[instr.args[0] as usize] >> 7;
-                self.registers[0xf] = msb_vy;
-                self.registers[instr.args[0] as usize] <<= 1;
+                let value = self.shift_operand(&instr);
+                self.set_with_flag(instr.args[0], value << 1, value >> 7);
 
                 self.pc += 2;
             },
@@ -259,7 +474,9 @@ impl Cpu {
             },
             Instructions::JpV0Addr => {
                 self.pc = u16::from(instr.args[0]) << 8 | u16::from(instr.args[1]);
-                self.pc += u16::from(self.registers[0x0]);
+                // The jump quirk reads the register from the top nibble of the address
+                let register = if self.quirks.jump { instr.args[0] as usize } else { 0 };
+                self.pc += u16::from(self.registers[register]);
             },
             Instructions::RndVxByte => {
                 let mut rng = rand::thread_rng();
@@ -269,7 +486,8 @@ impl Cpu {
                 self.pc += 2;
             },
             Instructions::SkpVx => {
-                let key_hex = self.registers[instr.args[0] as usize];
+                // Only the low nibble selects a key
+                let key_hex = self.registers[instr.args[0] as usize] & 0xF;
                 if pressed_keys[key_hex as usize] == 1 {
                     self.pc += 4;
                 } else {
@@ -277,7 +495,7 @@ impl Cpu {
                 }
             },
             Instructions::SknpVx => {
-                let key_hex = self.registers[instr.args[0] as usize];
+                let key_hex = self.registers[instr.args[0] as usize] & 0xF;
                 if pressed_keys[key_hex as usize] == 0 {
                     self.pc += 4;
                 } else {
@@ -285,26 +503,17 @@ impl Cpu {
                 }
             },
             Instructions::DrwVxVyNib => {
-                self.registers[0xf] = 0;
-
-                for i in 0..instr.args[2] {
-                    let line = self.ram[self.i as usize + i as usize];
-
-                    for pos in 0..8 {
-                        let mut value = line & (1 << (7 - pos));
-                        if value > 0 {
-                            value = 1;
-                        }
+                let x = self.registers[instr.args[0] as usize];
+                let y = self.registers[instr.args[1] as usize];
+                let sprite = &self.ram[self.memory_at_i(instr.args[2] as usize)?];
 
-                        let x = (self.registers[instr.args[0] as usize] + pos) % SCREEN_WIDTH as u8;
-                        let y = (self.registers[instr.args[1] as usize] + i) % SCREEN_HEIGHT as u8;
-
-                        let set_vf = screen.draw(x, y, value);
-                        if set_vf {
-                            self.registers[0xf] = 1;
-                        }
-                    }
-                }
+                let collision = if self.quirks.clip {
+                    screen.draw_sprite(x, y, &clip_sprite(x, y, sprite))
+                } else {
+                    screen.draw_sprite(x, y, sprite)
+                };
+                self.registers[0xf] = u8::from(collision);
+                self.waiting_for_vblank = self.quirks.vblank;
 
                 self.pc += 2;
             },
@@ -328,52 +537,1013 @@ impl Cpu {
             Instructions::LdStVx => {
                 self.st = self.registers[instr.args[0] as usize];
 
-                audio_device.enable_sound();
+                // A zero sound timer is silence, it also cuts off a beep that is still playing
+                if self.st > 0 {
+                    audio_device.enable_sound(self.frame);
+                } else {
+                    audio_device.disable_sound(self.frame);
+                }
 
                 self.pc += 2;
             },
             Instructions::AddIVx => {
-                self.i += u16::from(self.registers[instr.args[0] as usize]);
+                // Reading or writing past the end of memory through I is caught where it happens
+                self.i = self.i.wrapping_add(u16::from(self.registers[instr.args[0] as usize]));
 
                 self.pc += 2;
             },
             Instructions::LdFVx => {
-                self.i = u16::from(self.registers[instr.args[0] as usize] * 5);
+                // Only the low nibble selects a glyph
+                let digit = u16::from(self.registers[instr.args[0] as usize] & 0xF);
+                self.i = self.font_address + digit * GLYPH_SIZE;
 
                 self.pc += 2;
             },
             Instructions::LdBVx => {
-                let mut x = self.registers[instr.args[0] as usize];
+                let x = self.registers[instr.args[0] as usize];
+                let digits = self.memory_at_i(3)?;
 
-                let a = x.div_floor(100);
-                x -= a * 100;
-                let b = x.div_floor(10);
-                x -= b * 10;
-                let c = x;
-
-                self.ram[self.i as usize] = a;
-                self.ram[self.i as usize + 1] = b;
-                self.ram[self.i as usize + 2] = c;
+                // Hundreds, tens and ones
+                self.ram[digits].copy_from_slice(&[x / 100, x / 10 % 10, x % 10]);
 
                 self.pc += 2;
             },
             Instructions::LdIVx => {
                 let last_reg = instr.args[0] as usize;
-                for x in 0..=last_reg {
-                    self.ram[self.i as usize + x] = self.registers[x];
+                let memory = self.memory_at_i(last_reg + 1)?;
+                self.ram[memory].copy_from_slice(&self.registers[..=last_reg]);
+                if !self.quirks.load_store {
+                    self.i += last_reg as u16 + 1;
                 }
 
                 self.pc += 2;
             },
             Instructions::LdVxI => {
                 let last_reg = instr.args[0] as usize;
-                for x in 0..=last_reg {
-                    self.registers[x] = self.ram[self.i as usize + x];
+                let memory = self.memory_at_i(last_reg + 1)?;
+                self.registers[..=last_reg].copy_from_slice(&self.ram[memory]);
+                if !self.quirks.load_store {
+                    self.i += last_reg as u16 + 1;
                 }
 
                 self.pc += 2;
             },
-            _ => panic!("Unknown instruction {:?}", instr.int),
+            // 0NNN calls machine code on the original hardware, there's nothing to run it on here
+            Instructions::Sys => return Err(CpuError::UnsupportedOpcode { pc: self.pc, opcode: instr.opcode }),
         }
+
+        Ok(())
     }
-}
\ No newline at end of file
+
+    // Writes an ALU result and its flag. The flag normally goes last so VF holds it even when
+    // VF is the destination, the vf_order quirk makes the result win instead
+    fn set_with_flag(&mut self, register: u8, result: u8, flag: u8) {
+        if self.quirks.vf_order {
+            self.registers[0xf] = flag;
+            self.registers[register as usize] = result;
+        } else {
+            self.registers[register as usize] = result;
+            self.registers[0xf] = flag;
+        }
+    }
+
+    fn shift_operand(&self, instr: &Instruction) -> u8 {
+        let register = if self.quirks.shift { instr.args[0] } else { instr.args[1] };
+
+        self.registers[register as usize]
+    }
+}
+
+// Drops the rows and columns of a sprite that would wrap around the screen edges. Only the
+// position wraps, the sprite is drawn at x and y modulo the screen size
+fn clip_sprite(x: u8, y: u8, sprite: &[u8]) -> Vec<u8> {
+    let x = x as usize % SCREEN_WIDTH;
+    let y = y as usize % SCREEN_HEIGHT;
+    let visible_columns = (SCREEN_WIDTH - x).min(8);
+    let mask = (0xFF00u16 >> visible_columns) as u8;
+
+    sprite.iter()
+        .take(SCREEN_HEIGHT - y)
+        .map(|row| row & mask)
+        .collect()
+}
+
+// The font and the program can't share memory, whichever is written last would corrupt the other
+fn check_font_overlap(font_address: u16, rom: &Rom) -> Result<(), FontAddressError> {
+    let font = font_address as usize..font_address as usize + FONT_SIZE as usize;
+    let program = rom.load_address() as usize..rom.load_address() as usize + rom.data().len();
+    if font.start < program.end && program.start < font.end {
+        return Err(FontAddressError::OverlapsProgram(font_address));
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::headless_screen::HeadlessScreen;
+    use crate::null_sound_device::NullSoundDevice;
+
+    struct Machine {
+        cpu: Cpu,
+        screen: HeadlessScreen,
+        audio: NullSoundDevice,
+        pressed_keys: [u8; 16],
+        last_key: u8,
+    }
+
+    impl Machine {
+        fn new() -> Machine {
+            Machine {
+                cpu: Cpu::new(),
+                screen: HeadlessScreen::new(),
+                audio: NullSoundDevice::new(),
+                pressed_keys: [0; 16],
+                last_key: 255,
+            }
+        }
+
+        // Places the opcode at pc and runs it
+        fn exec(&mut self, opcode: u16) {
+            self.try_exec(opcode).unwrap();
+        }
+
+        fn try_exec(&mut self, opcode: u16) -> Result<(), CpuError> {
+            let pc = self.cpu.pc as usize;
+            self.cpu.ram[pc] = (opcode >> 8) as u8;
+            self.cpu.ram[pc + 1] = opcode as u8;
+
+            self.cpu.step(&mut self.screen, &mut self.audio, &self.pressed_keys, &mut self.last_key)
+        }
+
+        fn v(&self, reg: usize) -> u8 {
+            self.cpu.registers[reg]
+        }
+    }
+
+    #[test]
+    fn sys_is_not_executed() {
+        let mut m = Machine::new();
+        assert_eq!(m.try_exec(0x0123), Err(CpuError::UnsupportedOpcode { pc: 0x200, opcode: 0x0123 }));
+    }
+
+    #[test]
+    fn cls_clears_screen() {
+        let mut m = Machine::new();
+        m.screen.draw(3, 4, 1);
+
+        m.exec(0x00E0);
+
+        assert!(m.screen.framebuffer().iter().all(|p| *p == 0));
+        assert_eq!(m.cpu.pc, 0x202);
+    }
+
+    #[test]
+    fn call_and_ret() {
+        let mut m = Machine::new();
+
+        m.exec(0x2400);
+        assert_eq!(m.cpu.pc, 0x400);
+        assert_eq!(m.cpu.sp, 0);
+        assert_eq!(m.cpu.stack[0], 0x202);
+
+        m.exec(0x00EE);
+        assert_eq!(m.cpu.pc, 0x202);
+        assert_eq!(m.cpu.sp, 0xFF);
+    }
+
+    #[test]
+    fn call_and_ret_at_depth_16() {
+        let mut m = Machine::new();
+
+        for depth in 0..16u16 {
+            m.exec(0x2300 + depth * 0x10);
+        }
+        assert_eq!(m.cpu.sp, 0xF);
+        assert_eq!(m.cpu.pc, 0x3F0);
+
+        for depth in (0..16u16).rev() {
+            m.exec(0x00EE);
+            let caller = if depth == 0 { 0x200 } else { 0x300 + (depth - 1) * 0x10 };
+            assert_eq!(m.cpu.pc, caller + 2);
+        }
+        assert_eq!(m.cpu.sp, 0xFF);
+    }
+
+    #[test]
+    fn call_overflows_at_depth_17() {
+        let mut m = Machine::new();
+
+        for depth in 0..16u16 {
+            m.exec(0x2300 + depth * 0x10);
+        }
+        assert_eq!(m.try_exec(0x2400), Err(CpuError::StackOverflow { pc: 0x3F0 }));
+    }
+
+    #[test]
+    fn ret_with_empty_stack() {
+        let mut m = Machine::new();
+        assert_eq!(m.try_exec(0x00EE), Err(CpuError::StackUnderflow { pc: 0x200 }));
+    }
+
+    #[test]
+    fn memory_access_past_the_end_of_ram() {
+        let mut m = Machine::new();
+        m.cpu.i = 0xFFE;
+
+        assert_eq!(m.try_exec(0xF233), Err(CpuError::MemoryOutOfBounds { pc: 0x200, i: 0xFFE }));
+        assert_eq!(m.try_exec(0xD015), Err(CpuError::MemoryOutOfBounds { pc: 0x200, i: 0xFFE }));
+        m.exec(0xF155);
+        assert_eq!(m.cpu.ram[0xFFE..], [0, 0]);
+    }
+
+    #[test]
+    fn pc_past_the_end_of_ram() {
+        let mut m = Machine::new();
+        m.cpu.pc = 0xFFF;
+
+        let result = m.cpu.step(&mut m.screen, &mut m.audio, &m.pressed_keys, &mut m.last_key);
+        assert_eq!(result, Err(CpuError::PcOutOfBounds { pc: 0xFFF }));
+    }
+
+
+    #[test]
+    fn jp() {
+        let mut m = Machine::new();
+        m.exec(0x1ABC);
+        assert_eq!(m.cpu.pc, 0xABC);
+    }
+
+    #[test]
+    fn jp_v0_addr() {
+        let mut m = Machine::new();
+        m.cpu.registers[0] = 0x10;
+        m.exec(0xB300);
+        assert_eq!(m.cpu.pc, 0x310);
+    }
+
+    #[test]
+    fn se_vx_byte() {
+        let mut m = Machine::new();
+        m.cpu.registers[1] = 0x42;
+
+        m.exec(0x3142);
+        assert_eq!(m.cpu.pc, 0x204);
+        m.exec(0x3143);
+        assert_eq!(m.cpu.pc, 0x206);
+    }
+
+    #[test]
+    fn sne_vx_byte() {
+        let mut m = Machine::new();
+        m.cpu.registers[1] = 0x42;
+
+        m.exec(0x4142);
+        assert_eq!(m.cpu.pc, 0x202);
+        m.exec(0x4143);
+        assert_eq!(m.cpu.pc, 0x206);
+    }
+
+    #[test]
+    fn se_vx_vy() {
+        let mut m = Machine::new();
+        m.cpu.registers[1] = 7;
+        m.cpu.registers[2] = 7;
+        m.cpu.registers[3] = 8;
+
+        m.exec(0x5120);
+        assert_eq!(m.cpu.pc, 0x204);
+        m.exec(0x5130);
+        assert_eq!(m.cpu.pc, 0x206);
+    }
+
+    #[test]
+    fn sne_vx_vy() {
+        let mut m = Machine::new();
+        m.cpu.registers[1] = 7;
+        m.cpu.registers[2] = 7;
+        m.cpu.registers[3] = 8;
+
+        m.exec(0x9120);
+        assert_eq!(m.cpu.pc, 0x202);
+        m.exec(0x9130);
+        assert_eq!(m.cpu.pc, 0x206);
+    }
+
+    #[test]
+    fn ld_vx_byte() {
+        let mut m = Machine::new();
+        m.exec(0x6A5C);
+        assert_eq!(m.v(0xA), 0x5C);
+    }
+
+    #[test]
+    fn add_vx_byte_wraps_without_flag() {
+        let mut m = Machine::new();
+        m.cpu.registers[2] = 0xFF;
+        m.cpu.registers[0xF] = 0x7;
+
+        m.exec(0x7202);
+        assert_eq!(m.v(2), 0x01);
+        assert_eq!(m.v(0xF), 0x7);
+    }
+
+    #[test]
+    fn ld_vx_vy() {
+        let mut m = Machine::new();
+        m.cpu.registers[4] = 0x99;
+        m.exec(0x8340);
+        assert_eq!(m.v(3), 0x99);
+    }
+
+    #[test]
+    fn or_and_xor() {
+        let mut m = Machine::new();
+        m.cpu.registers[1] = 0b1100;
+        m.cpu.registers[2] = 0b1010;
+
+        m.exec(0x8121);
+        assert_eq!(m.v(1), 0b1110);
+
+        m.cpu.registers[1] = 0b1100;
+        m.exec(0x8122);
+        assert_eq!(m.v(1), 0b1000);
+
+        m.cpu.registers[1] = 0b1100;
+        m.exec(0x8123);
+        assert_eq!(m.v(1), 0b0110);
+    }
+
+    #[test]
+    fn add_vx_vy_carry() {
+        let mut m = Machine::new();
+        m.cpu.registers[1] = 0xF0;
+        m.cpu.registers[2] = 0x20;
+
+        m.exec(0x8124);
+        assert_eq!(m.v(1), 0x10);
+        assert_eq!(m.v(0xF), 1);
+
+        m.exec(0x8124);
+        assert_eq!(m.v(1), 0x30);
+        assert_eq!(m.v(0xF), 0);
+    }
+
+    #[test]
+    fn add_vx_vf() {
+        let mut m = Machine::new();
+        m.cpu.registers[1] = 0xFF;
+        m.cpu.registers[0xF] = 0x02;
+
+        m.exec(0x81F4);
+        assert_eq!(m.v(1), 0x01);
+        assert_eq!(m.v(0xF), 1);
+    }
+
+    #[test]
+    fn add_vf_vy_keeps_flag() {
+        let mut m = Machine::new();
+        m.cpu.registers[0xF] = 0xFF;
+        m.cpu.registers[1] = 0x02;
+
+        m.exec(0x8F14);
+        assert_eq!(m.v(0xF), 1);
+
+        m.cpu.registers[0xF] = 0x01;
+        m.exec(0x8F14);
+        assert_eq!(m.v(0xF), 0);
+    }
+
+    #[test]
+    fn sub_vx_vy() {
+        let mut m = Machine::new();
+        m.cpu.registers[1] = 0x30;
+        m.cpu.registers[2] = 0x10;
+
+        m.exec(0x8125);
+        assert_eq!(m.v(1), 0x20);
+        assert_eq!(m.v(0xF), 1);
+
+        m.cpu.registers[1] = 0x10;
+        m.cpu.registers[2] = 0x30;
+        m.exec(0x8125);
+        assert_eq!(m.v(1), 0xE0);
+        assert_eq!(m.v(0xF), 0);
+    }
+
+    #[test]
+    fn sub_equal_operands_sets_not_borrow() {
+        let mut m = Machine::new();
+        m.cpu.registers[1] = 0x42;
+        m.cpu.registers[2] = 0x42;
+
+        m.exec(0x8125);
+        assert_eq!(m.v(1), 0);
+        assert_eq!(m.v(0xF), 1);
+    }
+
+    #[test]
+    fn sub_vf_vy_keeps_flag() {
+        let mut m = Machine::new();
+        m.cpu.registers[0xF] = 0x30;
+        m.cpu.registers[1] = 0x10;
+
+        m.exec(0x8F15);
+        assert_eq!(m.v(0xF), 1);
+
+        m.cpu.registers[0xF] = 0x10;
+        m.cpu.registers[1] = 0x30;
+        m.exec(0x8F15);
+        assert_eq!(m.v(0xF), 0);
+    }
+
+    #[test]
+    fn sub_vx_vf_uses_original_vf() {
+        let mut m = Machine::new();
+        m.cpu.registers[1] = 0x30;
+        m.cpu.registers[0xF] = 0x10;
+
+        m.exec(0x81F5);
+        assert_eq!(m.v(1), 0x20);
+        assert_eq!(m.v(0xF), 1);
+
+        m.cpu.registers[1] = 0x10;
+        m.cpu.registers[0xF] = 0x30;
+        m.exec(0x81F5);
+        assert_eq!(m.v(1), 0xE0);
+        assert_eq!(m.v(0xF), 0);
+    }
+
+    #[test]
+    fn subn_vx_vy() {
+        let mut m = Machine::new();
+        m.cpu.registers[1] = 0x10;
+        m.cpu.registers[2] = 0x30;
+
+        m.exec(0x8127);
+        assert_eq!(m.v(1), 0x20);
+        assert_eq!(m.v(0xF), 1);
+
+        m.cpu.registers[1] = 0x30;
+        m.cpu.registers[2] = 0x10;
+        m.exec(0x8127);
+        assert_eq!(m.v(1), 0xE0);
+        assert_eq!(m.v(0xF), 0);
+    }
+
+    #[test]
+    fn subn_equal_operands_sets_not_borrow() {
+        let mut m = Machine::new();
+        m.cpu.registers[1] = 0x42;
+        m.cpu.registers[2] = 0x42;
+
+        m.exec(0x8127);
+        assert_eq!(m.v(1), 0);
+        assert_eq!(m.v(0xF), 1);
+    }
+
+    #[test]
+    fn subn_vf_vy_keeps_flag() {
+        let mut m = Machine::new();
+        m.cpu.registers[0xF] = 0x10;
+        m.cpu.registers[1] = 0x30;
+
+        m.exec(0x8F17);
+        assert_eq!(m.v(0xF), 1);
+
+        m.cpu.registers[0xF] = 0x30;
+        m.cpu.registers[1] = 0x10;
+        m.exec(0x8F17);
+        assert_eq!(m.v(0xF), 0);
+    }
+
+    #[test]
+    fn subn_vx_vf_uses_original_vf() {
+        let mut m = Machine::new();
+        m.cpu.registers[1] = 0x10;
+        m.cpu.registers[0xF] = 0x30;
+
+        m.exec(0x81F7);
+        assert_eq!(m.v(1), 0x20);
+        assert_eq!(m.v(0xF), 1);
+    }
+
+    #[test]
+    fn shr_vx() {
+        let mut m = Machine::new();
+        m.cpu.registers[1] = 0b0000_0101;
+        m.cpu.registers[2] = 0xFF;
+
+        m.exec(0x8126);
+        assert_eq!(m.v(1), 0b0000_0010);
+        assert_eq!(m.v(0xF), 1);
+
+        m.exec(0x8126);
+        assert_eq!(m.v(1), 0b0000_0001);
+        assert_eq!(m.v(0xF), 0);
+    }
+
+    #[test]
+    fn shl_vx() {
+        let mut m = Machine::new();
+        m.cpu.registers[1] = 0b1000_0001;
+        m.cpu.registers[2] = 0xFF;
+
+        m.exec(0x812E);
+        assert_eq!(m.v(1), 0b0000_0010);
+        assert_eq!(m.v(0xF), 1);
+
+        m.exec(0x812E);
+        assert_eq!(m.v(1), 0b0000_0100);
+        assert_eq!(m.v(0xF), 0);
+    }
+
+    #[test]
+    fn shr_and_shl_vf_keep_flag() {
+        let mut m = Machine::new();
+
+        m.cpu.registers[0xF] = 0b0000_0011;
+        m.exec(0x8F06);
+        assert_eq!(m.v(0xF), 1);
+
+        m.cpu.registers[0xF] = 0b0000_0010;
+        m.exec(0x8F06);
+        assert_eq!(m.v(0xF), 0);
+
+        m.cpu.registers[0xF] = 0b1100_0000;
+        m.exec(0x8F0E);
+        assert_eq!(m.v(0xF), 1);
+
+        m.cpu.registers[0xF] = 0b0100_0000;
+        m.exec(0x8F0E);
+        assert_eq!(m.v(0xF), 0);
+    }
+
+    #[test]
+    fn ld_i_addr() {
+        let mut m = Machine::new();
+        m.exec(0xA123);
+        assert_eq!(m.cpu.i, 0x123);
+    }
+
+    #[test]
+    fn rnd_vx_byte_is_masked() {
+        let mut m = Machine::new();
+
+        m.exec(0xC100);
+        assert_eq!(m.v(1), 0);
+
+        for _ in 0..32 {
+            m.exec(0xC10F);
+            assert!(m.v(1) <= 0x0F);
+        }
+    }
+
+    #[test]
+    fn drw_draws_and_detects_collision() {
+        let mut m = Machine::new();
+        m.cpu.ram[0x300] = 0b1000_0001;
+        m.cpu.i = 0x300;
+        m.cpu.registers[1] = 10;
+        m.cpu.registers[2] = 5;
+
+        m.exec(0xD121);
+        assert!(m.screen.pixel(10, 5));
+        assert!(!m.screen.pixel(11, 5));
+        assert!(m.screen.pixel(17, 5));
+        assert_eq!(m.v(0xF), 0);
+
+        m.exec(0xD121);
+        assert!(!m.screen.pixel(10, 5));
+        assert!(!m.screen.pixel(17, 5));
+        assert_eq!(m.v(0xF), 1);
+    }
+
+    #[test]
+    fn drw_wraps_around_screen() {
+        let mut m = Machine::new();
+        m.cpu.ram[0x300] = 0xFF;
+        m.cpu.ram[0x301] = 0xFF;
+        m.cpu.i = 0x300;
+        m.cpu.registers[1] = 60;
+        m.cpu.registers[2] = 31;
+
+        m.exec(0xD122);
+        assert!(m.screen.pixel(63, 31));
+        assert!(m.screen.pixel(0, 31));
+        assert!(m.screen.pixel(3, 0));
+        assert!(!m.screen.pixel(4, 0));
+    }
+
+    #[test]
+    fn skp_and_sknp() {
+        let mut m = Machine::new();
+        m.cpu.registers[1] = 0xA;
+
+        m.exec(0xE19E);
+        assert_eq!(m.cpu.pc, 0x202);
+        m.exec(0xE1A1);
+        assert_eq!(m.cpu.pc, 0x206);
+
+        m.pressed_keys[0xA] = 1;
+        m.exec(0xE19E);
+        assert_eq!(m.cpu.pc, 0x20A);
+        m.exec(0xE1A1);
+        assert_eq!(m.cpu.pc, 0x20C);
+    }
+
+    #[test]
+    fn skp_and_sknp_use_low_nibble_only() {
+        let mut m = Machine::new();
+        m.cpu.registers[1] = 0xFA;
+        m.pressed_keys[0xA] = 1;
+
+        m.exec(0xE19E);
+        assert_eq!(m.cpu.pc, 0x204);
+        m.exec(0xE1A1);
+        assert_eq!(m.cpu.pc, 0x206);
+    }
+
+    #[test]
+    fn delay_timer() {
+        let mut m = Machine::new();
+        m.cpu.registers[1] = 3;
+
+        m.exec(0xF115);
+        assert_eq!(m.cpu.dt, 3);
+
+        m.cpu.tick(&mut m.audio);
+        m.exec(0xF207);
+        assert_eq!(m.v(2), 2);
+    }
+
+    #[test]
+    fn sound_timer_drives_audio() {
+        let mut m = Machine::new();
+        m.cpu.registers[1] = 2;
+
+        m.exec(0xF118);
+        assert_eq!(m.cpu.st, 2);
+        assert!(m.audio.playing);
+
+        m.cpu.tick(&mut m.audio);
+        assert!(m.audio.playing);
+        m.cpu.tick(&mut m.audio);
+        assert!(!m.audio.playing);
+    }
+
+    #[test]
+    fn ld_vx_k_waits_for_key() {
+        let mut m = Machine::new();
+
+        m.exec(0xF30A);
+        assert_eq!(m.cpu.pc, 0x200);
+
+        m.last_key = 0x7;
+        m.exec(0xF30A);
+        assert_eq!(m.cpu.pc, 0x202);
+        assert_eq!(m.v(3), 0x7);
+        assert_eq!(m.last_key, 255);
+    }
+
+    #[test]
+    fn add_i_vx() {
+        let mut m = Machine::new();
+        m.cpu.i = 0x100;
+        m.cpu.registers[1] = 0x20;
+
+        m.exec(0xF11E);
+        assert_eq!(m.cpu.i, 0x120);
+    }
+
+    #[test]
+    fn add_i_vx_wraps_around() {
+        let mut m = Machine::new();
+        m.cpu.i = 0xFFF0;
+        m.cpu.registers[1] = 0x20;
+
+        m.exec(0xF11E);
+        assert_eq!(m.cpu.i, 0x0010);
+    }
+
+    #[test]
+    fn ld_f_vx_points_at_font() {
+        let mut m = Machine::new();
+        m.cpu.load_font(FontSet::Schip);
+        m.cpu.registers[1] = 0xA;
+
+        m.exec(0xF129);
+        assert_eq!(m.cpu.i, DEFAULT_FONT_ADDRESS + 0xA * 5);
+        assert_eq!(m.cpu.ram[m.cpu.i as usize..m.cpu.i as usize + 5], [0xF0, 0x90, 0xF0, 0x90, 0x90]);
+    }
+
+    #[test]
+    fn ld_f_vx_uses_low_nibble_only() {
+        let mut m = Machine::new();
+        m.cpu.registers[1] = 0xFA;
+
+        m.exec(0xF129);
+        assert_eq!(m.cpu.i, DEFAULT_FONT_ADDRESS + 0xA * 5);
+    }
+
+    #[test]
+    fn load_preserves_font() {
+        let mut m = Machine::new();
+        m.cpu.load_font(FontSet::Schip);
+        m.cpu.ram[0x900] = 0xAA;
+
+        let rom = Rom::from_bytes(vec![0x12, 0x34], 0x600).unwrap();
+        m.cpu.load(&rom).unwrap();
+
+        assert_eq!(m.cpu.ram[0x50..0x55], [0xF0, 0x90, 0x90, 0x90, 0xF0]);
+        assert_eq!(m.cpu.ram[0x600..0x602], [0x12, 0x34]);
+        assert_eq!(m.cpu.ram[0x900], 0);
+        assert_eq!(m.cpu.pc, 0x600);
+    }
+
+    #[test]
+    fn font_at_custom_address() {
+        let mut m = Machine::new();
+        m.cpu.set_font_address(0x000).unwrap();
+        m.cpu.load_font(FontSet::CosmacVip);
+        m.cpu.registers[1] = 0x7;
+
+        m.exec(0xF129);
+        assert_eq!(m.cpu.i, 0x7 * 5);
+        assert_eq!(m.cpu.ram[0x23..0x28], [0xF0, 0x10, 0x10, 0x10, 0x10]);
+        assert_eq!(m.cpu.ram[0x50], 0);
+    }
+
+    #[test]
+    fn ld_b_vx() {
+        let mut m = Machine::new();
+        m.cpu.i = 0x300;
+        m.cpu.registers[1] = 234;
+
+        m.exec(0xF133);
+        assert_eq!(m.cpu.ram[0x300..0x303], [2, 3, 4]);
+
+        m.cpu.registers[1] = 7;
+        m.exec(0xF133);
+        assert_eq!(m.cpu.ram[0x300..0x303], [0, 0, 7]);
+
+        m.cpu.registers[1] = 250;
+        m.exec(0xF133);
+        assert_eq!(m.cpu.ram[0x300..0x303], [2, 5, 0]);
+    }
+
+    #[test]
+    fn ld_i_vx_and_ld_vx_i() {
+        let mut m = Machine::new();
+        m.cpu.i = 0x300;
+        m.cpu.registers[..4].copy_from_slice(&[1, 2, 3, 4]);
+
+        m.exec(0xF255);
+        assert_eq!(m.cpu.ram[0x300..0x304], [1, 2, 3, 0]);
+
+        m.cpu.registers = [0; 16];
+        m.exec(0xF265);
+        assert_eq!(m.cpu.registers[..4], [1, 2, 3, 0]);
+    }
+
+    #[test]
+    fn shift_quirk_off_shifts_vy() {
+        let mut m = Machine::new();
+        m.cpu.quirks.shift = false;
+        m.cpu.registers[1] = 0xFF;
+        m.cpu.registers[2] = 0b1000_0011;
+
+        m.exec(0x8126);
+        assert_eq!(m.v(1), 0b0100_0001);
+        assert_eq!(m.v(0xF), 1);
+
+        m.exec(0x812E);
+        assert_eq!(m.v(1), 0b0000_0110);
+        assert_eq!(m.v(0xF), 1);
+    }
+
+    #[test]
+    fn load_store_quirk_off_increments_i() {
+        let mut m = Machine::new();
+        m.cpu.quirks.load_store = false;
+        m.cpu.i = 0x300;
+
+        m.exec(0xF255);
+        assert_eq!(m.cpu.i, 0x303);
+        m.exec(0xF065);
+        assert_eq!(m.cpu.i, 0x304);
+    }
+
+    #[test]
+    fn vf_order_quirk_keeps_result_in_vf() {
+        let mut m = Machine::new();
+        m.cpu.quirks.vf_order = true;
+        m.cpu.registers[0xF] = 0xFF;
+        m.cpu.registers[1] = 0x02;
+
+        m.exec(0x8F14);
+        assert_eq!(m.v(0xF), 0x01);
+    }
+
+    #[test]
+    fn logic_quirk_resets_vf() {
+        let mut m = Machine::new();
+        m.cpu.quirks.logic = true;
+
+        for opcode in [0x8121, 0x8122, 0x8123] {
+            m.cpu.registers[0xF] = 1;
+            m.exec(opcode);
+            assert_eq!(m.v(0xF), 0);
+        }
+    }
+
+    #[test]
+    fn jump_quirk_uses_vx() {
+        let mut m = Machine::new();
+        m.cpu.quirks.jump = true;
+        m.cpu.registers[0] = 0x10;
+        m.cpu.registers[3] = 0x02;
+
+        m.exec(0xB300);
+        assert_eq!(m.cpu.pc, 0x302);
+    }
+
+    #[test]
+    fn clip_quirk_cuts_sprites_at_the_edges() {
+        let mut m = Machine::new();
+        m.cpu.quirks.clip = true;
+        m.cpu.i = 0x300;
+        m.cpu.ram[0x300..0x302].copy_from_slice(&[0xFF, 0xFF]);
+        m.cpu.registers[1] = 60;
+        m.cpu.registers[2] = 31;
+
+        m.exec(0xD122);
+        assert!(m.screen.pixel(63, 31));
+        assert!(!m.screen.pixel(0, 31));
+        assert!(!m.screen.pixel(60, 0));
+
+        // The position itself still wraps
+        m.cpu.registers[1] = 64;
+        m.cpu.registers[2] = 32;
+        m.exec(0xD121);
+        assert!(m.screen.pixel(0, 0));
+    }
+
+    #[test]
+    fn vblank_quirk_ends_the_frame_after_drawing() {
+        let mut m = Machine::new();
+        m.cpu.quirks.vblank = true;
+        // DRW then an endless loop of ADD V1, 1
+        m.cpu.ram[0x200..0x206].copy_from_slice(&[0xD0, 0x01, 0x71, 0x01, 0x12, 0x02]);
+
+        crate::frame::run_frame(&mut m.cpu, &mut m.screen, &mut m.audio, &m.pressed_keys, &mut m.last_key).unwrap();
+        assert_eq!(m.cpu.pc, 0x202);
+
+        crate::frame::run_frame(&mut m.cpu, &mut m.screen, &mut m.audio, &m.pressed_keys, &mut m.last_key).unwrap();
+        assert_eq!(m.v(1), 4);
+    }
+
+
+    #[test]
+    fn soft_reset_restores_program_and_font() {
+        let mut m = Machine::new();
+        m.cpu.load_font(FontSet::default());
+        m.cpu.load(&Rom::from_bytes(vec![0x22, 0x04, 0x00, 0x00, 0xA3, 0x00], 0x200).unwrap()).unwrap();
+
+        m.exec(0x2204);
+        m.exec(0xA300);
+        m.cpu.ram[0x300] = 0xF0;
+        m.exec(0xD001);
+        assert!(m.screen.pixel(0, 0));
+        m.exec(0x6305);
+        m.exec(0xF318);
+        assert!(m.audio.playing);
+
+        m.cpu.ram[0x050] = 0xFF;
+        m.cpu.ram[0x201] = 0xFF;
+        m.cpu.ram[0x1FF] = 0x01;
+        m.cpu.dt = 10;
+
+        m.cpu.soft_reset(&mut m.screen, &mut m.audio);
+        assert_eq!(m.cpu.pc, 0x200);
+        assert_eq!(m.cpu.sp, 0xFF);
+        assert_eq!(m.cpu.stack, [0; 16]);
+        assert_eq!(m.cpu.registers, [0; 16]);
+        assert_eq!((m.cpu.i, m.cpu.dt, m.cpu.st), (0, 0, 0));
+        assert_eq!(m.cpu.ram[0x200..0x206], [0x22, 0x04, 0x00, 0x00, 0xA3, 0x00]);
+        assert_eq!(m.cpu.ram[0x050..0x055], FontSet::default().bytes()[..5]);
+        assert_eq!(m.cpu.ram[0x1FF], 0x01);
+        assert!(m.screen.framebuffer().iter().all(|p| *p == 0));
+        assert!(!m.audio.playing);
+    }
+
+    #[test]
+    fn load_rejects_a_program_overlapping_the_font() {
+        let mut m = Machine::new();
+        m.cpu.load_font(FontSet::default());
+        m.cpu.load(&Rom::from_bytes(vec![0x12, 0x00], 0x200).unwrap()).unwrap();
+
+        let result = m.cpu.load(&Rom::from_bytes(vec![0xFF; 0x10], 0x09F).unwrap());
+        assert_eq!(result, Err(FontAddressError::OverlapsProgram(0x050)));
+        assert_eq!(m.cpu.rom().unwrap().load_address(), 0x200);
+        assert_eq!(m.cpu.ram[0x050..0x0A0], FontSet::default().bytes());
+
+        m.cpu.set_font_address(0x300).unwrap();
+        assert!(m.cpu.load(&Rom::from_bytes(vec![0xFF; 0x10], 0x000).unwrap()).is_ok());
+        let result = m.cpu.load(&Rom::from_bytes(vec![0xFF; 0x200], 0x200).unwrap());
+        assert_eq!(result, Err(FontAddressError::OverlapsProgram(0x300)));
+    }
+
+    #[test]
+    fn set_font_address_moves_the_font() {
+        let mut m = Machine::new();
+        m.cpu.load_font(FontSet::default());
+        m.cpu.load(&Rom::from_bytes(vec![0x12, 0x00], 0x200).unwrap()).unwrap();
+
+        m.cpu.set_font_address(0x000).unwrap();
+        assert_eq!(m.cpu.ram[0x000..0x050], FontSet::default().bytes());
+        assert!(m.cpu.ram[0x050..0x0A0].iter().all(|b| *b == 0));
+        m.cpu.registers[1] = 0xA;
+        m.exec(0xF129);
+        assert_eq!(m.cpu.i, 0x032);
+
+        assert_eq!(m.cpu.set_font_address(0xFB1), Err(FontAddressError::OutsideMemory(0xFB1)));
+        assert_eq!(m.cpu.set_font_address(0x1B1), Err(FontAddressError::OverlapsProgram(0x1B1)));
+        assert!(m.cpu.set_font_address(0xFB0).is_ok());
+        assert_eq!(m.cpu.font_address(), 0xFB0);
+    }
+
+    #[test]
+    fn load_keeps_a_font_above_the_load_address() {
+        let mut m = Machine::new();
+        m.cpu.set_font_address(0x300).unwrap();
+        m.cpu.load_font(FontSet::default());
+        m.cpu.ram[0x2FF] = 0xAA;
+
+        m.cpu.load(&Rom::from_bytes(vec![0x12, 0x00], 0x200).unwrap()).unwrap();
+        assert_eq!(m.cpu.ram[0x300..0x350], FontSet::default().bytes());
+        assert_eq!(m.cpu.ram[0x2FF], 0);
+    }
+
+    #[test]
+    fn hard_reset_clears_all_memory() {
+        let mut m = Machine::new();
+        m.cpu.load_font(FontSet::default());
+        m.cpu.load(&Rom::from_bytes(vec![0x12, 0x00], 0x200).unwrap()).unwrap();
+        m.cpu.ram[0x1FF] = 0x01;
+        m.cpu.ram[0x000] = 0x01;
+
+        m.cpu.hard_reset(&mut m.screen, &mut m.audio);
+        assert_eq!(m.cpu.ram[0x1FF], 0);
+        assert_eq!(m.cpu.ram[0x000], 0);
+        assert_eq!(m.cpu.ram[0x050..0x055], FontSet::default().bytes()[..5]);
+        assert_eq!(m.cpu.ram[0x200..0x202], [0x12, 0x00]);
+    }
+
+    // Remembers every buzzer call with its timestamp
+    #[derive(Default)]
+    struct Recorder {
+        events: Vec<(u64, bool)>,
+    }
+
+    impl Audible for Recorder {
+        fn enable_sound(&mut self, frame: u64) {
+            self.events.push((frame, true));
+        }
+
+        fn disable_sound(&mut self, frame: u64) {
+            self.events.push((frame, false));
+        }
+    }
+
+    #[test]
+    fn buzzer_events_are_stamped_with_emulated_frames() {
+        let mut m = Machine::new();
+        let mut recorder = Recorder::default();
+        m.cpu.registers[1] = 3;
+
+        m.cpu.tick(&mut recorder);
+        m.cpu.ram[0x200..0x202].copy_from_slice(&[0xF1, 0x18]);
+        m.cpu.step(&mut m.screen, &mut recorder, &m.pressed_keys, &mut m.last_key).unwrap();
+        for _ in 0..5 {
+            m.cpu.tick(&mut recorder);
+        }
+
+        assert_eq!(recorder.events, [(1, true), (4, false)]);
+    }
+
+    #[test]
+    fn ld_st_zero_is_silent() {
+        let mut m = Machine::new();
+        m.exec(0xF018);
+        assert!(!m.audio.playing);
+
+        m.cpu.registers[1] = 10;
+        m.exec(0xF118);
+        assert!(m.audio.playing);
+        m.exec(0xF018);
+        assert!(!m.audio.playing);
+        assert_eq!(m.cpu.st, 0);
+    }
+}
@@ -1,5 +1,49 @@
+use crate::constants::{SCREEN_WIDTH, SCREEN_HEIGHT};
+
 pub trait Drawable {
     fn draw(&mut self, x: u8, y: u8, set: u8) -> bool;
     fn cls(&mut self);
     fn present(&mut self);
+
+    // Draws an 8 pixel wide sprite, one byte per row, wrapping around the screen edges.
+    // Returns true if any lit pixel got erased. Backends should override this with something
+    // faster than going pixel by pixel
+    fn draw_sprite(&mut self, x: u8, y: u8, rows: &[u8]) -> bool {
+        let mut collision = false;
+
+        for (row, line) in rows.iter().enumerate() {
+            for col in 0..8 {
+                if line & (0x80 >> col) == 0 {
+                    continue;
+                }
+
+                let x = (x as usize + col) % SCREEN_WIDTH;
+                let y = (y as usize + row) % SCREEN_HEIGHT;
+
+                collision |= self.draw(x as u8, y as u8, 1);
+            }
+        }
+
+        collision
+    }
+
+    // Same as draw_sprite but for 16x16 SCHIP sprites, one u16 per row
+    fn draw_sprite_16(&mut self, x: u8, y: u8, rows: &[u16]) -> bool {
+        let mut collision = false;
+
+        for (row, line) in rows.iter().enumerate() {
+            for col in 0..16 {
+                if line & (0x8000 >> col) == 0 {
+                    continue;
+                }
+
+                let x = (x as usize + col) % SCREEN_WIDTH;
+                let y = (y as usize + row) % SCREEN_HEIGHT;
+
+                collision |= self.draw(x as u8, y as u8, 1);
+            }
+        }
+
+        collision
+    }
 }
@@ -1,3 +1,6 @@
+use std::error::Error;
+use std::fmt;
+
 static ZERO: [u8; 5] = [0xF0, 0x90, 0x90, 0x90, 0xF0];
 static ONE: [u8; 5] = [0x20, 0x60, 0x20, 0x20, 0x70];
 static TWO: [u8; 5] = [0xF0, 0x10, 0xF0, 0x80, 0xF0];
@@ -16,3 +19,80 @@ pub static E: [u8; 5] = [0xF0, 0x80, 0xF0, 0x80, 0xF0];
 pub static F: [u8; 5] = [0xF0, 0x80, 0xF0, 0x80, 0x80];
 
 pub static FONT_TABLE: [&[u8]; 16] = [&ZERO, &ONE, &TWO, &THREE, &FOUR, &FIVE, &SIX, &SEVEN, &EIGHT, &NINE, &A, &B, &C, &D, &E, &F];
+
+// Fonts of the original interpreters, as dumped from their ROMs
+pub static COSMAC_VIP_FONT: [[u8; 5]; 16] = [
+    [0xF0, 0x90, 0x90, 0x90, 0xF0], [0x60, 0x20, 0x20, 0x20, 0x70], [0xF0, 0x10, 0xF0, 0x80, 0xF0], [0xF0, 0x10, 0x70, 0x10, 0xF0],
+    [0xA0, 0xA0, 0xF0, 0x20, 0x20], [0xF0, 0x80, 0xF0, 0x10, 0xF0], [0xF0, 0x80, 0xF0, 0x90, 0xF0], [0xF0, 0x10, 0x10, 0x10, 0x10],
+    [0xF0, 0x90, 0xF0, 0x90, 0xF0], [0xF0, 0x90, 0xF0, 0x10, 0xF0], [0xF0, 0x90, 0xF0, 0x90, 0x90], [0xF0, 0x50, 0x70, 0x50, 0xF0],
+    [0xF0, 0x80, 0x80, 0x80, 0xF0], [0xF0, 0x50, 0x50, 0x50, 0xF0], [0xF0, 0x80, 0xF0, 0x80, 0xF0], [0xF0, 0x80, 0xF0, 0x80, 0x80],
+];
+
+pub static DREAM_6800_FONT: [[u8; 5]; 16] = [
+    [0xE0, 0xA0, 0xA0, 0xA0, 0xE0], [0x40, 0x40, 0x40, 0x40, 0x40], [0xE0, 0x20, 0xE0, 0x80, 0xE0], [0xE0, 0x20, 0xE0, 0x20, 0xE0],
+    [0x80, 0xA0, 0xA0, 0xE0, 0x20], [0xE0, 0x80, 0xE0, 0x20, 0xE0], [0xE0, 0x80, 0xE0, 0xA0, 0xE0], [0xE0, 0x20, 0x20, 0x20, 0x20],
+    [0xE0, 0xA0, 0xE0, 0xA0, 0xE0], [0xE0, 0xA0, 0xE0, 0x20, 0xE0], [0xE0, 0xA0, 0xE0, 0xA0, 0xA0], [0xC0, 0xA0, 0xE0, 0xA0, 0xC0],
+    [0xE0, 0x80, 0x80, 0x80, 0xE0], [0xC0, 0xA0, 0xA0, 0xA0, 0xC0], [0xE0, 0x80, 0xE0, 0x80, 0xE0], [0xE0, 0x80, 0xC0, 0x80, 0x80],
+];
+
+pub static ETI_660_FONT: [[u8; 5]; 16] = [
+    [0xE0, 0xA0, 0xA0, 0xA0, 0xE0], [0x20, 0x20, 0x20, 0x20, 0x20], [0xE0, 0x20, 0xE0, 0x80, 0xE0], [0xE0, 0x20, 0xE0, 0x20, 0xE0],
+    [0xA0, 0xA0, 0xE0, 0x20, 0x20], [0xE0, 0x80, 0xE0, 0x20, 0xE0], [0xE0, 0x80, 0xE0, 0xA0, 0xE0], [0xE0, 0x20, 0x20, 0x20, 0x20],
+    [0xE0, 0xA0, 0xE0, 0xA0, 0xE0], [0xE0, 0xA0, 0xE0, 0x20, 0xE0], [0xE0, 0xA0, 0xE0, 0xA0, 0xA0], [0xC0, 0xA0, 0xE0, 0xA0, 0xC0],
+    [0xE0, 0x80, 0x80, 0x80, 0xE0], [0xC0, 0xA0, 0xA0, 0xA0, 0xC0], [0xE0, 0x80, 0xE0, 0x80, 0xE0], [0xE0, 0x80, 0xC0, 0x80, 0x80],
+];
+
+// Where the interpreter keeps the font, most modern interpreters use 0x050
+pub const DEFAULT_FONT_ADDRESS: u16 = 0x050;
+pub const GLYPH_SIZE: u16 = 5;
+// All 16 glyphs
+pub const FONT_SIZE: u16 = 16 * GLYPH_SIZE;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FontAddressError {
+    OutsideMemory(u16),
+    OverlapsProgram(u16),
+}
+
+impl fmt::Display for FontAddressError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FontAddressError::OutsideMemory(address) => write!(f, "Font at {address:#X} doesn't fit in memory"),
+            FontAddressError::OverlapsProgram(address) => write!(f, "Font at {address:#X} overlaps the program"),
+        }
+    }
+}
+
+impl Error for FontAddressError {}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum FontSet {
+    CosmacVip,
+    Dream6800,
+    Eti660,
+    // The 4x5 SCHIP font, also the one from Cowgod's reference and what most interpreters ship
+    #[default]
+    Schip,
+}
+
+impl FontSet {
+    pub fn from_name(name: &str) -> Option<FontSet> {
+        match name {
+            "vip" => Some(FontSet::CosmacVip),
+            "dream6800" => Some(FontSet::Dream6800),
+            "eti660" => Some(FontSet::Eti660),
+            "schip" => Some(FontSet::Schip),
+            _ => None,
+        }
+    }
+
+    // Glyphs 0-F one after another, GLYPH_SIZE bytes each
+    pub fn bytes(&self) -> Vec<u8> {
+        match self {
+            FontSet::CosmacVip => COSMAC_VIP_FONT.concat(),
+            FontSet::Dream6800 => DREAM_6800_FONT.concat(),
+            FontSet::Eti660 => ETI_660_FONT.concat(),
+            FontSet::Schip => FONT_TABLE.concat(),
+        }
+    }
+}
@@ -0,0 +1,39 @@
+use std::time::{Duration, Instant};
+
+use crate::audible::Audible;
+use crate::constants::FRAMES_PER_SECOND;
+use crate::cpu::{Cpu, CpuError};
+use crate::drawable::Drawable;
+
+pub fn run_frame(cpu: &mut Cpu, screen: &mut dyn Drawable, audio_device: &mut dyn Audible, pressed_keys: &[u8; 16], last_key: &mut u8) -> Result<(), CpuError> {
+    cpu.tick(audio_device);
+
+    let mut result = Ok(());
+    for _ in 0..cpu.instructions_per_frame {
+        if cpu.waiting_for_vblank() {
+            break;
+        }
+
+        result = cpu.step(screen, audio_device, pressed_keys, last_key);
+        if result.is_err() {
+            break;
+        }
+    }
+
+    // Frontends only push the framebuffer out once per 60 Hz frame, not after every instruction.
+    // Still done after an error, so the screen shows what the program drew up to it
+    screen.present();
+
+    result
+}
+
+pub fn wait_for_next_frame(next_frame: &mut Instant) {
+    *next_frame += Duration::new(0, 1_000_000_000u32 / FRAMES_PER_SECOND);
+
+    let now = Instant::now();
+    if *next_frame > now {
+        ::std::thread::sleep(*next_frame - now);
+    } else {
+        *next_frame = now;
+    }
+}
@@ -0,0 +1,47 @@
+use std::error::Error;
+use std::fmt;
+use std::io;
+
+use crate::capture::CaptureError;
+use crate::cpu::CpuError;
+
+// Why a frontend stopped early. Returned rather than exiting on the spot, so recordings and the
+// terminal are cleaned up by their Drop impls on the way out
+#[derive(Debug)]
+pub enum FrontendError {
+    AudioRecording(hound::Error),
+    Capture(CaptureError),
+    Terminal(io::Error),
+    Emulation(CpuError),
+}
+
+impl fmt::Display for FrontendError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FrontendError::AudioRecording(e) => write!(f, "Failed to create audio recording: {e}"),
+            FrontendError::Capture(e) => write!(f, "Failed to start recording: {e}"),
+            FrontendError::Terminal(e) => write!(f, "Failed to set up terminal: {e}"),
+            FrontendError::Emulation(e) => write!(f, "Emulation stopped: {e}"),
+        }
+    }
+}
+
+impl Error for FrontendError {}
+
+impl From<hound::Error> for FrontendError {
+    fn from(e: hound::Error) -> Self {
+        FrontendError::AudioRecording(e)
+    }
+}
+
+impl From<CaptureError> for FrontendError {
+    fn from(e: CaptureError) -> Self {
+        FrontendError::Capture(e)
+    }
+}
+
+impl From<CpuError> for FrontendError {
+    fn from(e: CpuError) -> Self {
+        FrontendError::Emulation(e)
+    }
+}
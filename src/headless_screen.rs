@@ -0,0 +1,51 @@
+use crate::drawable::Drawable;
+use crate::constants::{SCREEN_WIDTH, SCREEN_HEIGHT};
+
+// Keeps the framebuffer in memory only, for tests and running without any display
+pub struct HeadlessScreen {
+    fb: [u8; SCREEN_HEIGHT * SCREEN_WIDTH],
+    frames: u64,
+}
+
+impl Drawable for HeadlessScreen {
+    fn draw(&mut self, x: u8, y: u8, set: u8) -> bool {
+        let index = (y as usize * SCREEN_WIDTH) + x as usize;
+        let collision = self.fb[index] & set;
+        self.fb[index] ^= set;
+
+        collision > 0
+    }
+
+    fn cls(&mut self) {
+        self.fb.iter_mut().for_each(|m| *m = 0);
+    }
+
+    fn present(&mut self) {
+        self.frames += 1;
+    }
+}
+
+impl HeadlessScreen {
+    pub fn new() -> HeadlessScreen {
+        HeadlessScreen { fb: [0; SCREEN_HEIGHT * SCREEN_WIDTH], frames: 0 }
+    }
+
+    pub fn pixel(&self, x: usize, y: usize) -> bool {
+        self.fb[(y * SCREEN_WIDTH) + x] > 0
+    }
+
+    pub fn framebuffer(&self) -> &[u8] {
+        &self.fb
+    }
+
+    // Number of times present was called
+    pub fn frames(&self) -> u64 {
+        self.frames
+    }
+}
+
+impl Default for HeadlessScreen {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -35,4 +35,22 @@ pub enum Instructions {
     LdBVx,
     LdIVx,
     LdVxI,
-}
\ No newline at end of file
+}
+impl Instructions {
+    pub const ALL: [Instructions; 35] = [
+        Instructions::Sys, Instructions::Cls, Instructions::Ret, Instructions::Jp, Instructions::Call,
+        Instructions::SeVxByte, Instructions::SneVxByte, Instructions::SeVxVy, Instructions::LdVxByte,
+        Instructions::AddVxByte, Instructions::LdVxVy, Instructions::OrVxVy, Instructions::AndVxVy,
+        Instructions::XorVxVy, Instructions::AddVxVy, Instructions::SubVxVy, Instructions::ShrVxVy,
+        Instructions::SubnVxVy, Instructions::ShlVxVy, Instructions::SneVxVy, Instructions::LdIAddr,
+        Instructions::JpV0Addr, Instructions::RndVxByte, Instructions::DrwVxVyNib, Instructions::SkpVx,
+        Instructions::SknpVx, Instructions::LdVxDt, Instructions::LdVxK, Instructions::LdDtVx,
+        Instructions::LdStVx, Instructions::AddIVx, Instructions::LdFVx, Instructions::LdBVx,
+        Instructions::LdIVx, Instructions::LdVxI,
+    ];
+
+    // The instruction with that variant name, in any case: "LdIAddr", "ldiaddr"
+    pub fn from_name(name: &str) -> Option<Instructions> {
+        Instructions::ALL.into_iter().find(|instruction| format!("{instruction:?}").eq_ignore_ascii_case(name))
+    }
+}
@@ -0,0 +1,45 @@
+#![forbid(unsafe_code)]
+
+#[macro_use]
+extern crate lazy_static;
+
+#[cfg(feature = "sdl")]
+extern crate sdl2;
+
+pub mod cpu;
+pub mod instructions;
+pub mod opcode_decoders;
+pub mod drawable;
+pub mod audible;
+pub mod tone;
+pub mod buzzer;
+#[cfg(feature = "sdl")]
+pub mod sdl_screen;
+#[cfg(feature = "sdl")]
+pub mod sdl_sound_device;
+#[cfg(feature = "sdl")]
+pub mod sdl_frontend;
+pub mod headless_screen;
+pub mod null_sound_device;
+pub mod wav_recorder;
+pub mod font;
+pub mod rom;
+pub mod archive;
+pub mod cartridge;
+pub mod octo;
+pub mod quirks;
+pub mod constants;
+pub mod palette;
+pub mod options;
+pub mod frame;
+pub mod frontend_error;
+pub mod tracer;
+pub mod capture;
+pub mod scaling;
+pub mod overlay;
+pub mod rom_browser;
+pub mod memory_viewer;
+pub mod tui_screen;
+pub mod tui_sound_device;
+pub mod tui_keypad;
+pub mod tui_frontend;
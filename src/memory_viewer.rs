@@ -0,0 +1,436 @@
+use crate::constants::MEMORY_SIZE;
+use crate::cpu::Cpu;
+use crate::overlay::{Overlay, CHAR_WIDTH, LINE_HEIGHT};
+
+const BACKGROUND_COLOR: u32 = 0xE0000000;
+const TEXT_COLOR: u32 = 0xFFC0C0C0;
+const LABEL_COLOR: u32 = 0xFF80A0FF;
+const TITLE_COLOR: u32 = 0xFFFFFFFF;
+const CURSOR_COLOR: u32 = 0xFF606060;
+const EDIT_COLOR: u32 = 0xFFA04040;
+const INVALID_COLOR: u32 = 0xFFC00000;
+const PC_COLOR: u32 = 0xFF405020;
+const I_COLOR: u32 = 0xFF203860;
+
+const BYTES_PER_ROW: usize = 16;
+const DUMP_ROWS: usize = 16;
+// Bytes highlighted from I on, enough for the largest (16x16) sprite
+const I_REGION_SIZE: usize = 32;
+// Text columns: 3 digit address, then the bytes, then the register list
+const DUMP_COLUMN: usize = 4;
+const REGISTER_COLUMN: usize = DUMP_COLUMN + BYTES_PER_ROW * 3 + 2;
+const STACK_ROW: usize = DUMP_ROWS + 1;
+const STACK_PER_ROW: usize = 8;
+const HELP_ROW: usize = STACK_ROW + 3;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Focus {
+    Memory,
+    Registers,
+}
+
+// Registers in the order Up and Down step through them: V0-VF in the register list, then the
+// ones shown in the title line
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Register {
+    V(usize),
+    Pc,
+    I,
+    Sp,
+    Dt,
+    St,
+}
+
+const REGISTERS: [Register; 21] = [
+    Register::V(0x0), Register::V(0x1), Register::V(0x2), Register::V(0x3),
+    Register::V(0x4), Register::V(0x5), Register::V(0x6), Register::V(0x7),
+    Register::V(0x8), Register::V(0x9), Register::V(0xA), Register::V(0xB),
+    Register::V(0xC), Register::V(0xD), Register::V(0xE), Register::V(0xF),
+    Register::Pc, Register::I, Register::Sp, Register::Dt, Register::St,
+];
+
+impl Register {
+    // Hex digits typed for a new value
+    fn digits(self) -> usize {
+        match self {
+            Register::Pc | Register::I => 3,
+            _ => 2,
+        }
+    }
+
+    fn value(self, cpu: &Cpu) -> u16 {
+        match self {
+            Register::V(index) => u16::from(cpu.registers[index]),
+            Register::Pc => cpu.pc,
+            Register::I => cpu.i,
+            Register::Sp => u16::from(cpu.sp),
+            Register::Dt => u16::from(cpu.dt),
+            Register::St => u16::from(cpu.st),
+        }
+    }
+
+    // Writes the value if the register can hold it: PC has to leave room for a whole instruction
+    // and SP has to point into the stack or be 0xFF for an empty one
+    fn set(self, cpu: &mut Cpu, value: u16) -> bool {
+        match self {
+            Register::V(index) => cpu.registers[index] = value as u8,
+            Register::Pc if (value as usize) < MEMORY_SIZE - 1 => cpu.pc = value,
+            Register::I => cpu.i = value,
+            Register::Sp if value < 0x10 || value == 0xFF => cpu.sp = value as u8,
+            Register::Dt => cpu.dt = value as u8,
+            Register::St => cpu.st = value as u8,
+            _ => return false,
+        }
+
+        true
+    }
+
+    // Where the value is drawn, V registers in the list and the others in the title line
+    fn cell(self) -> (usize, usize) {
+        match self {
+            Register::V(index) => (REGISTER_COLUMN + 3, index + 1),
+            Register::Pc => (3, 0),
+            Register::I => (10, 0),
+            Register::Sp => (18, 0),
+            Register::Dt => (25, 0),
+            Register::St => (32, 0),
+        }
+    }
+}
+
+// A hex dump of RAM with the registers and stack next to it. Bytes and registers can be edited
+// by typing their hex digits, the frontend keeps the emulator paused while it is open
+pub struct MemoryViewer {
+    focus: Focus,
+    // Address of the selected byte and index into REGISTERS of the selected register
+    cursor: usize,
+    register: usize,
+    // Value and count of the digits typed so far for the byte or register being edited
+    pending: Option<(u16, usize)>,
+    // Set when the last value typed was out of range for its register, until the next key
+    invalid: bool,
+    // Address of the first row shown
+    top: usize,
+}
+
+impl MemoryViewer {
+    pub fn new(cpu: &Cpu) -> MemoryViewer {
+        let mut viewer = MemoryViewer {
+            focus: Focus::Memory,
+            cursor: 0,
+            register: 0,
+            pending: None,
+            invalid: false,
+            top: 0,
+        };
+        viewer.jump_to_pc(cpu);
+
+        viewer
+    }
+
+    pub fn up(&mut self) {
+        match self.focus {
+            Focus::Memory => self.move_cursor(-(BYTES_PER_ROW as isize)),
+            Focus::Registers => self.register = self.register.saturating_sub(1),
+        }
+        self.cancel_edit();
+    }
+
+    pub fn down(&mut self) {
+        match self.focus {
+            Focus::Memory => self.move_cursor(BYTES_PER_ROW as isize),
+            Focus::Registers => self.register = (self.register + 1).min(REGISTERS.len() - 1),
+        }
+        self.cancel_edit();
+    }
+
+    pub fn left(&mut self) {
+        if self.focus == Focus::Memory {
+            self.move_cursor(-1);
+        }
+        self.cancel_edit();
+    }
+
+    pub fn right(&mut self) {
+        if self.focus == Focus::Memory {
+            self.move_cursor(1);
+        }
+        self.cancel_edit();
+    }
+
+    pub fn page_up(&mut self) {
+        self.move_cursor(-((BYTES_PER_ROW * DUMP_ROWS) as isize));
+        self.cancel_edit();
+    }
+
+    pub fn page_down(&mut self) {
+        self.move_cursor((BYTES_PER_ROW * DUMP_ROWS) as isize);
+        self.cancel_edit();
+    }
+
+    // Switches editing between memory and the registers
+    pub fn toggle_focus(&mut self) {
+        self.focus = match self.focus {
+            Focus::Memory => Focus::Registers,
+            Focus::Registers => Focus::Memory,
+        };
+        self.cancel_edit();
+    }
+
+    pub fn jump_to_pc(&mut self, cpu: &Cpu) {
+        self.jump_to(cpu.pc);
+    }
+
+    pub fn jump_to_i(&mut self, cpu: &Cpu) {
+        self.jump_to(cpu.i);
+    }
+
+    // Takes one hex digit. The last digit of a byte or register writes the value and moves on to
+    // the next one, a value the register can't hold is dropped and the register stays selected
+    pub fn edit(&mut self, cpu: &mut Cpu, digit: u8) {
+        self.invalid = false;
+        let (value, count) = self.pending.take().unwrap_or((0, 0));
+        let (value, count) = ((value << 4) | u16::from(digit & 0xF), count + 1);
+        if count < self.digits() {
+            self.pending = Some((value, count));
+            return;
+        }
+
+        match self.focus {
+            Focus::Memory => {
+                cpu.ram[self.cursor] = value as u8;
+                self.cursor = (self.cursor + 1).min(MEMORY_SIZE - 1);
+            },
+            Focus::Registers => {
+                if REGISTERS[self.register].set(cpu, value) {
+                    self.register = (self.register + 1).min(REGISTERS.len() - 1);
+                } else {
+                    self.invalid = true;
+                }
+            },
+        }
+    }
+
+    pub fn render(&mut self, cpu: &Cpu, overlay: &mut Overlay) {
+        overlay.clear(BACKGROUND_COLOR);
+
+        // Register::cell has the columns of the values
+        let title = format!("PC {:03X}  I {:03X}  SP {:02X}  DT {:02X}  ST {:02X}", cpu.pc, cpu.i, cpu.sp, cpu.dt, cpu.st);
+        overlay.draw_text(0, 0, &title, TITLE_COLOR);
+
+        // Keep the cursor row on screen
+        let row_of_cursor = self.cursor - self.cursor % BYTES_PER_ROW;
+        if row_of_cursor < self.top {
+            self.top = row_of_cursor;
+        } else if row_of_cursor >= self.top + BYTES_PER_ROW * DUMP_ROWS {
+            self.top = row_of_cursor + BYTES_PER_ROW - BYTES_PER_ROW * DUMP_ROWS;
+        }
+
+        let pc = cpu.pc as usize;
+        let i = cpu.i as usize;
+        for row in 0..DUMP_ROWS {
+            let address = self.top + row * BYTES_PER_ROW;
+            if address >= MEMORY_SIZE {
+                break;
+            }
+            overlay.draw_text(0, row + 1, &format!("{address:03X}"), LABEL_COLOR);
+
+            for (offset, byte) in cpu.ram[address..address + BYTES_PER_ROW].iter().enumerate() {
+                let address = address + offset;
+                let column = DUMP_COLUMN + offset * 3;
+
+                let highlight = if self.focus == Focus::Memory && address == self.cursor {
+                    Some(self.cursor_color())
+                } else if (pc..pc + 2).contains(&address) {
+                    Some(PC_COLOR)
+                } else if (i..i + I_REGION_SIZE).contains(&address) {
+                    Some(I_COLOR)
+                } else {
+                    None
+                };
+                if let Some(color) = highlight {
+                    highlight_cell(overlay, column, row + 1, 2, color);
+                }
+
+                let text = match self.pending {
+                    Some(pending) if self.focus == Focus::Memory && address == self.cursor => pending_text(pending, 2),
+                    _ => format!("{byte:02X}"),
+                };
+                overlay.draw_text(column, row + 1, &text, TEXT_COLOR);
+            }
+        }
+
+        for (index, value) in cpu.registers.iter().enumerate() {
+            let (column, row) = Register::V(index).cell();
+            overlay.draw_text(REGISTER_COLUMN, row, &format!("V{index:X}"), LABEL_COLOR);
+            overlay.draw_text(column, row, &format!("{value:02X}"), TEXT_COLOR);
+        }
+
+        // The selected register is drawn again over its background, with the digits typed so far
+        if self.focus == Focus::Registers {
+            let register = REGISTERS[self.register];
+            let (column, row) = register.cell();
+            let digits = register.digits();
+            let text = match self.pending {
+                Some(pending) => pending_text(pending, digits),
+                None => format!("{:0digits$X}", register.value(cpu)),
+            };
+            highlight_cell(overlay, column, row, digits, self.cursor_color());
+            overlay.draw_text(column, row, &text, if row == 0 { TITLE_COLOR } else { TEXT_COLOR });
+        }
+
+        // SP 0xFF means the stack is empty
+        let depth = if cpu.sp < 16 { cpu.sp as usize + 1 } else { 0 };
+        overlay.draw_text(0, STACK_ROW, "STACK", LABEL_COLOR);
+        for (index, address) in cpu.stack[..depth].iter().enumerate() {
+            let column = 6 + (index % STACK_PER_ROW) * 5;
+            overlay.draw_text(column, STACK_ROW + index / STACK_PER_ROW, &format!("{address:03X}"), TEXT_COLOR);
+        }
+
+        overlay.draw_text(0, HELP_ROW, "ARROWS/PGUP/PGDN MOVE  0-F EDIT  TAB REGISTERS  P/I GO TO PC/I", LABEL_COLOR);
+    }
+
+    fn jump_to(&mut self, address: u16) {
+        self.cursor = address as usize % MEMORY_SIZE;
+        self.focus = Focus::Memory;
+        self.cancel_edit();
+    }
+
+    fn cancel_edit(&mut self) {
+        self.pending = None;
+        self.invalid = false;
+    }
+
+    // Digits that make up a value of the selected byte or register
+    fn digits(&self) -> usize {
+        match self.focus {
+            Focus::Memory => 2,
+            Focus::Registers => REGISTERS[self.register].digits(),
+        }
+    }
+
+    fn cursor_color(&self) -> u32 {
+        if self.invalid {
+            INVALID_COLOR
+        } else if self.pending.is_some() {
+            EDIT_COLOR
+        } else {
+            CURSOR_COLOR
+        }
+    }
+
+    fn move_cursor(&mut self, by: isize) {
+        self.cursor = self.cursor.saturating_add_signed(by).min(MEMORY_SIZE - 1);
+    }
+}
+
+// Background behind `length` characters starting at (column, row)
+fn highlight_cell(overlay: &mut Overlay, column: usize, row: usize, length: usize, color: u32) {
+    let x = (column * CHAR_WIDTH).saturating_sub(1);
+    let y = (row * LINE_HEIGHT).saturating_sub(1);
+    overlay.fill_rect(x, y, CHAR_WIDTH * length + 1, LINE_HEIGHT, color);
+}
+
+// The digits typed so far followed by a _ for each one still missing
+fn pending_text((value, count): (u16, usize), digits: usize) -> String {
+    format!("{value:0count$X}{}", "_".repeat(digits - count))
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn two_digits_edit_a_byte_and_move_on() {
+        let mut cpu = Cpu::new();
+        let mut viewer = MemoryViewer::new(&cpu);
+
+        viewer.edit(&mut cpu, 0xA);
+        assert_eq!(cpu.ram[0x200], 0);
+        viewer.edit(&mut cpu, 0x2);
+        viewer.edit(&mut cpu, 0xF);
+        viewer.edit(&mut cpu, 0xF);
+        assert_eq!(cpu.ram[0x200..0x202], [0xA2, 0xFF]);
+        assert_eq!(viewer.cursor, 0x202);
+    }
+
+    #[test]
+    fn registers_are_edited_after_switching_focus() {
+        let mut cpu = Cpu::new();
+        let mut viewer = MemoryViewer::new(&cpu);
+
+        viewer.toggle_focus();
+        viewer.down();
+        viewer.edit(&mut cpu, 0x4);
+        viewer.edit(&mut cpu, 0x2);
+        assert_eq!(cpu.registers[1], 0x42);
+        assert_eq!(cpu.ram[0x200], 0);
+    }
+
+    #[test]
+    fn pc_i_sp_and_timers_are_edited_with_their_ranges() {
+        let mut cpu = Cpu::new();
+        let mut viewer = MemoryViewer::new(&cpu);
+        viewer.toggle_focus();
+        for _ in 0..16 {
+            viewer.down();
+        }
+
+        // PC takes three digits and has to leave room for an instruction
+        for digit in [0xF, 0xF, 0xF] {
+            viewer.edit(&mut cpu, digit);
+        }
+        assert_eq!(cpu.pc, 0x200);
+        assert!(viewer.invalid);
+        for digit in [0x3, 0x4, 0x6] {
+            viewer.edit(&mut cpu, digit);
+        }
+        assert_eq!(cpu.pc, 0x346);
+
+        for digit in [0xA, 0xB, 0xC] {
+            viewer.edit(&mut cpu, digit);
+        }
+        assert_eq!(cpu.i, 0xABC);
+
+        // SP points into the stack or is 0xFF for an empty one
+        viewer.edit(&mut cpu, 0x2);
+        viewer.edit(&mut cpu, 0x0);
+        assert_eq!(cpu.sp, 0xFF);
+        viewer.edit(&mut cpu, 0x0);
+        viewer.edit(&mut cpu, 0x3);
+        assert_eq!(cpu.sp, 0x03);
+
+        for digit in [0x3, 0xC, 0x0, 0x5] {
+            viewer.edit(&mut cpu, digit);
+        }
+        assert_eq!((cpu.dt, cpu.st), (0x3C, 0x05));
+
+        // ST stays selected, it is highlighted in the title line
+        let mut overlay = Overlay::new(256, 128);
+        viewer.render(&cpu, &mut overlay);
+        assert!(overlay.texels()[..256 * LINE_HEIGHT].contains(&CURSOR_COLOR));
+    }
+
+    #[test]
+    fn cursor_stays_in_memory_and_on_screen() {
+        let mut cpu = Cpu::new();
+        cpu.i = 0xFFF;
+        let mut viewer = MemoryViewer::new(&cpu);
+        let mut overlay = Overlay::new(256, 128);
+
+        viewer.jump_to_i(&cpu);
+        viewer.page_down();
+        viewer.right();
+        assert_eq!(viewer.cursor, 0xFFF);
+
+        viewer.render(&cpu, &mut overlay);
+        assert_eq!(viewer.top, 0xF00);
+
+        viewer.page_up();
+        viewer.page_up();
+        viewer.up();
+        viewer.render(&cpu, &mut overlay);
+        assert_eq!(viewer.top, 0xDE0);
+    }
+}
@@ -0,0 +1,23 @@
+use crate::audible::Audible;
+
+// Plays nothing, only remembers whether sound is currently on
+#[derive(Default)]
+pub struct NullSoundDevice {
+    pub playing: bool,
+}
+
+impl NullSoundDevice {
+    pub fn new() -> NullSoundDevice {
+        NullSoundDevice { playing: false }
+    }
+}
+
+impl Audible for NullSoundDevice {
+    fn enable_sound(&mut self, _frame: u64) {
+        self.playing = true;
+    }
+
+    fn disable_sound(&mut self, _frame: u64) {
+        self.playing = false;
+    }
+}
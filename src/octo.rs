@@ -0,0 +1,765 @@
+use std::collections::HashMap;
+
+use crate::constants::MEMORY_SIZE;
+use crate::rom::RomError;
+
+// Instructions and directives of SUPER-CHIP and XO-CHIP, and the parts of Octo's metaprogramming
+// that would need an expression evaluator. They are reported instead of being taken for labels
+const UNSUPPORTED: [&str; 23] = [
+    "hires", "lores", "exit", "scroll-down", "scroll-up", "scroll-left", "scroll-right",
+    "saveflags", "loadflags", "bighex", "long", "plane", "audio", "pitch",
+    ":macro", ":calc", ":stringmode", ":assert", ":pointer", ":proto", "{", "}", ":include",
+];
+
+struct Token<'a> {
+    text: &'a str,
+    line: usize,
+}
+
+// Where a label's address has to be filled in once the label is defined
+#[derive(Clone, Copy)]
+enum Fixup {
+    // The low 12 bits of the instruction at the position
+    Address,
+    // The two `v0 := ` and `v1 := ` instructions of :unpack
+    Unpack,
+}
+
+enum Block {
+    // Position of the jump over the branch that is open
+    If { jump: usize, has_else: bool },
+    // Address to jump back to and positions of the jumps out of the loop
+    Loop { start: u16, breaks: Vec<usize> },
+}
+
+#[derive(Clone, Copy)]
+enum Operand {
+    Register(u8),
+    Byte(u8),
+}
+
+enum Comparison {
+    Equal(Operand),
+    NotEqual(Operand),
+    Less(Operand),
+    Greater(Operand),
+    LessOrEqual(Operand),
+    GreaterOrEqual(Operand),
+    Key,
+    NotKey,
+}
+
+// Assembles Octo source into CHIP-8 bytecode starting at `load_address`. All of Octo's CHIP-8
+// statements, control flow and the :alias, :const, :org, :byte, :next and :unpack directives are
+// understood. Anything for SUPER-CHIP or XO-CHIP, macros and :calc expressions are reported with
+// RomError::UnsupportedOctoSource
+pub fn assemble(source: &str, load_address: u16) -> Result<Vec<u8>, RomError> {
+    let tokens = source.lines()
+        .enumerate()
+        .flat_map(|(index, line)| {
+            let code = line.split('#').next().unwrap_or("");
+            code.split_whitespace().map(move |text| Token { text, line: index + 1 })
+        })
+        .collect();
+
+    Assembler::new(tokens, load_address).run()
+}
+
+struct Assembler<'a> {
+    tokens: Vec<Token<'a>>,
+    next: usize,
+    load_address: u16,
+    program: Vec<u8>,
+    // Where the next byte goes, :org moves it
+    position: usize,
+    labels: HashMap<&'a str, u16>,
+    constants: HashMap<&'a str, i32>,
+    aliases: HashMap<&'a str, u8>,
+    fixups: Vec<(usize, Fixup, &'a str, usize)>,
+    blocks: Vec<Block>,
+    // Like Octo, the program starts with a jump to main, which is dropped again when main
+    // turns out to come first
+    jump_to_main: bool,
+}
+
+impl<'a> Assembler<'a> {
+    fn new(tokens: Vec<Token<'a>>, load_address: u16) -> Assembler<'a> {
+        Assembler {
+            tokens,
+            next: 0,
+            load_address,
+            program: vec![0; 2],
+            position: 2,
+            labels: HashMap::new(),
+            constants: HashMap::new(),
+            aliases: HashMap::new(),
+            fixups: vec![],
+            blocks: vec![],
+            jump_to_main: true,
+        }
+    }
+
+    fn run(mut self) -> Result<Vec<u8>, RomError> {
+        while self.next < self.tokens.len() {
+            self.statement()?;
+        }
+
+        if let Some(block) = self.blocks.last() {
+            let name = match block {
+                Block::If { .. } => "if without end",
+                Block::Loop { .. } => "loop without again",
+            };
+            return Err(self.error(name));
+        }
+
+        let main = *self.labels.get("main").ok_or_else(|| RomError::Cartridge("program has no main label".to_string()))?;
+        if self.jump_to_main {
+            self.program[..2].copy_from_slice(&(0x1000 | main).to_be_bytes());
+        }
+
+        for (position, fixup, label, line) in std::mem::take(&mut self.fixups) {
+            let address = *self.labels.get(label)
+                .ok_or_else(|| RomError::Cartridge(format!("line {line}: undefined label {label:?}")))?;
+            match fixup {
+                Fixup::Address => self.patch(position, address),
+                Fixup::Unpack => {
+                    self.program[position + 1] |= (address >> 8) as u8;
+                    self.program[position + 3] = address as u8;
+                },
+            }
+        }
+
+        Ok(self.program)
+    }
+
+    fn statement(&mut self) -> Result<(), RomError> {
+        let token = self.token()?;
+        if UNSUPPORTED.contains(&token) {
+            return Err(RomError::UnsupportedOctoSource(token.to_string()));
+        }
+
+        match token {
+            ":" => {
+                let name = self.token()?;
+                self.define(name)?;
+            },
+            ":next" => {
+                // Names the second byte of the next instruction, for self modifying code
+                let name = self.token()?;
+                let address = self.address()? + 1;
+                self.labels.insert(name, address);
+            },
+            ":alias" => {
+                let name = self.token()?;
+                let register = self.register()?;
+                self.aliases.insert(name, register);
+            },
+            ":const" => {
+                let name = self.token()?;
+                let value = self.value()?;
+                self.constants.insert(name, value);
+            },
+            ":org" => {
+                let address = self.value()?;
+                if !(self.load_address as i32..MEMORY_SIZE as i32).contains(&address) {
+                    return Err(self.error(&format!("can't :org to {address:#X}")));
+                }
+                self.position = (address - self.load_address as i32) as usize;
+            },
+            ":byte" => {
+                let byte = self.byte()?;
+                self.emit(&[byte]);
+            },
+            ":unpack" => {
+                let nibble = self.value()?;
+                if !(0..=0xF).contains(&nibble) {
+                    return Err(self.error("the first :unpack value has to be a nibble"));
+                }
+                let target = self.target(Fixup::Unpack)?;
+                self.emit(&[0x60, (nibble as u8) << 4 | (target >> 8) as u8, 0x61, target as u8]);
+            },
+            ":call" => self.address_instruction(0x2000, Fixup::Address)?,
+            ":breakpoint" => {
+                self.token()?;
+            },
+            ":monitor" => {
+                self.token()?;
+                self.token()?;
+            },
+            "clear" => self.emit_instruction(0x00E0),
+            "return" | ";" => self.emit_instruction(0x00EE),
+            "jump" => self.address_instruction(0x1000, Fixup::Address)?,
+            "jump0" => self.address_instruction(0xB000, Fixup::Address)?,
+            "bcd" => self.register_instruction(0xF033)?,
+            "save" => self.register_range_instruction(0xF055)?,
+            "load" => self.register_range_instruction(0xF065)?,
+            "sprite" => {
+                let x = self.register()?;
+                let y = self.register()?;
+                let height = self.value()?;
+                if !(0..=0xF).contains(&height) {
+                    return Err(self.error("sprite height has to be 0 to 15"));
+                }
+                self.emit_instruction(0xD000 | u16::from(x) << 8 | u16::from(y) << 4 | height as u16);
+            },
+            "delay" | "buzzer" => {
+                self.expect(":=")?;
+                let register = u16::from(self.register()?);
+                let low = if token == "delay" { 0x15 } else { 0x18 };
+                self.emit_instruction(0xF000 | register << 8 | low);
+            },
+            "i" => self.index_statement()?,
+            "if" => {
+                let (register, comparison) = self.condition()?;
+                match self.token()? {
+                    "then" => self.skip(register, &comparison, false),
+                    "begin" => {
+                        self.skip(register, &comparison, true);
+                        let jump = self.emit_jump();
+                        self.blocks.push(Block::If { jump, has_else: false });
+                    },
+                    other => return Err(self.error(&format!("expected then or begin, found {other:?}"))),
+                }
+            },
+            "else" => {
+                let Some(Block::If { jump, has_else: false }) = self.blocks.pop() else {
+                    return Err(self.error("else without if ... begin"));
+                };
+                let end_jump = self.emit_jump();
+                self.patch(jump, self.address()?);
+                self.blocks.push(Block::If { jump: end_jump, has_else: true });
+            },
+            "end" => {
+                let Some(Block::If { jump, .. }) = self.blocks.pop() else {
+                    return Err(self.error("end without if ... begin"));
+                };
+                self.patch(jump, self.address()?);
+            },
+            "loop" => {
+                let start = self.address()?;
+                self.blocks.push(Block::Loop { start, breaks: vec![] });
+            },
+            "while" => {
+                let (register, comparison) = self.condition()?;
+                self.skip(register, &comparison, true);
+                let jump = self.emit_jump();
+                match self.blocks.iter_mut().rev().find(|block| matches!(block, Block::Loop { .. })) {
+                    Some(Block::Loop { breaks, .. }) => breaks.push(jump),
+                    _ => return Err(self.error("while outside of a loop")),
+                }
+            },
+            "again" => {
+                let Some(Block::Loop { start, breaks }) = self.blocks.pop() else {
+                    return Err(self.error("again without loop"));
+                };
+                self.emit_instruction(0x1000 | start);
+                let end = self.address()?;
+                for jump in breaks {
+                    self.patch(jump, end);
+                }
+            },
+            _ => {
+                if let Some(register) = self.alias_or_register(token) {
+                    return self.register_statement(register);
+                }
+                if let Some(value) = self.number(token) {
+                    let byte = self.to_byte(value)?;
+                    self.emit(&[byte]);
+                    return Ok(());
+                }
+
+                // Anything else is a call to a label
+                self.next -= 1;
+                self.address_instruction(0x2000, Fixup::Address)?;
+            },
+        }
+
+        Ok(())
+    }
+
+    fn index_statement(&mut self) -> Result<(), RomError> {
+        match self.token()? {
+            ":=" => {
+                if self.peek() == Some("hex") {
+                    self.next += 1;
+                    return self.register_instruction(0xF029);
+                }
+                self.address_instruction(0xA000, Fixup::Address)
+            },
+            "+=" => self.register_instruction(0xF01E),
+            other => Err(self.error(&format!("unknown operator {other:?} for i"))),
+        }
+    }
+
+    fn register_statement(&mut self, x: u8) -> Result<(), RomError> {
+        let x = u16::from(x) << 8;
+        let operator = self.token()?;
+
+        if operator == ":=" {
+            match self.peek() {
+                Some("random") => {
+                    self.next += 1;
+                    let mask = self.byte()?;
+                    self.emit_instruction(0xC000 | x | u16::from(mask));
+                    return Ok(());
+                },
+                Some("delay") => {
+                    self.next += 1;
+                    self.emit_instruction(0xF007 | x);
+                    return Ok(());
+                },
+                Some("key") => {
+                    self.next += 1;
+                    self.emit_instruction(0xF00A | x);
+                    return Ok(());
+                },
+                _ => {},
+            }
+        }
+
+        let operand = self.operand()?;
+        let instruction = match (operator, operand) {
+            (":=", Operand::Byte(byte)) => 0x6000 | x | u16::from(byte),
+            ("+=", Operand::Byte(byte)) => 0x7000 | x | u16::from(byte),
+            ("-=", Operand::Byte(byte)) => 0x7000 | x | u16::from(byte.wrapping_neg()),
+            (_, Operand::Register(y)) => {
+                let low = match operator {
+                    ":=" => 0x0,
+                    "|=" => 0x1,
+                    "&=" => 0x2,
+                    "^=" => 0x3,
+                    "+=" => 0x4,
+                    "-=" => 0x5,
+                    ">>=" => 0x6,
+                    "=-" => 0x7,
+                    "<<=" => 0xE,
+                    _ => return Err(self.error(&format!("unknown operator {operator:?}"))),
+                };
+                0x8000 | x | u16::from(y) << 4 | low
+            },
+            _ => return Err(self.error(&format!("operator {operator:?} needs a register"))),
+        };
+        self.emit_instruction(instruction);
+
+        Ok(())
+    }
+
+    fn condition(&mut self) -> Result<(u8, Comparison), RomError> {
+        let register = self.register()?;
+        let comparison = match self.token()? {
+            "key" => Comparison::Key,
+            "-key" => Comparison::NotKey,
+            "==" => Comparison::Equal(self.operand()?),
+            "!=" => Comparison::NotEqual(self.operand()?),
+            "<" => Comparison::Less(self.operand()?),
+            ">" => Comparison::Greater(self.operand()?),
+            "<=" => Comparison::LessOrEqual(self.operand()?),
+            ">=" => Comparison::GreaterOrEqual(self.operand()?),
+            other => return Err(self.error(&format!("unknown comparison {other:?}"))),
+        };
+
+        Ok((register, comparison))
+    }
+
+    // Emits the instructions that skip the next one when the comparison comes out as `when`
+    fn skip(&mut self, register: u8, comparison: &Comparison, when: bool) {
+        let x = u16::from(register) << 8;
+        let (equal, operand) = match *comparison {
+            Comparison::Key | Comparison::NotKey => {
+                let pressed = matches!(comparison, Comparison::Key) == when;
+                self.emit_instruction(x | if pressed { 0xE09E } else { 0xE0A1 });
+                return;
+            },
+            Comparison::Equal(operand) => (when, operand),
+            Comparison::NotEqual(operand) => (!when, operand),
+            Comparison::Less(operand) | Comparison::Greater(operand)
+            | Comparison::LessOrEqual(operand) | Comparison::GreaterOrEqual(operand) => {
+                // vF gets the carry flag of a subtraction, which is 1 for vx >= operand after
+                // vF =- vx and 1 for operand >= vx after vF -= vx
+                self.emit_instruction(match operand {
+                    Operand::Register(y) => 0x8F00 | u16::from(y) << 4,
+                    Operand::Byte(byte) => 0x6F00 | u16::from(byte),
+                });
+                let (subtract, flag) = match comparison {
+                    Comparison::Less(_) => (0x8F07, 0),
+                    Comparison::GreaterOrEqual(_) => (0x8F07, 1),
+                    Comparison::Greater(_) => (0x8F05, 0),
+                    _ => (0x8F05, 1),
+                };
+                self.emit_instruction(subtract | x >> 4);
+                self.emit_instruction(if when { 0x3F00 } else { 0x4F00 } | flag);
+                return;
+            },
+        };
+
+        self.emit_instruction(match (operand, equal) {
+            (Operand::Byte(byte), true) => 0x3000 | x | u16::from(byte),
+            (Operand::Byte(byte), false) => 0x4000 | x | u16::from(byte),
+            (Operand::Register(y), true) => 0x5000 | x | u16::from(y) << 4,
+            (Operand::Register(y), false) => 0x9000 | x | u16::from(y) << 4,
+        });
+    }
+
+    fn define(&mut self, name: &'a str) -> Result<(), RomError> {
+        if name == "main" && self.jump_to_main && self.position == 2 && self.program.len() == 2 {
+            // Nothing before main, so no jump needed. Labels already defined here move with it
+            self.program.clear();
+            self.position = 0;
+            self.jump_to_main = false;
+            let start = self.load_address + 2;
+            for address in self.labels.values_mut().filter(|address| **address == start) {
+                *address = self.load_address;
+            }
+        }
+
+        if self.labels.contains_key(name) {
+            return Err(self.error(&format!("label {name:?} defined twice")));
+        }
+        let address = self.address()?;
+        self.labels.insert(name, address);
+
+        Ok(())
+    }
+
+    fn address_instruction(&mut self, opcode: u16, fixup: Fixup) -> Result<(), RomError> {
+        let target = self.target(fixup)?;
+        self.emit_instruction(opcode | target);
+
+        Ok(())
+    }
+
+    // An address operand: a number, constant or label. Labels that come later are filled in at the end
+    fn target(&mut self, fixup: Fixup) -> Result<u16, RomError> {
+        let token = self.token()?;
+        if let Some(value) = self.number(token) {
+            if !(0..MEMORY_SIZE as i32).contains(&value) {
+                return Err(self.error(&format!("address {value:#X} is outside of memory")));
+            }
+            return Ok(value as u16);
+        }
+        if let Some(address) = self.labels.get(token) {
+            return Ok(*address);
+        }
+        if UNSUPPORTED.contains(&token) {
+            return Err(RomError::UnsupportedOctoSource(token.to_string()));
+        }
+
+        let line = self.tokens[self.next - 1].line;
+        self.fixups.push((self.position, fixup, token, line));
+        Ok(0)
+    }
+
+    fn register_instruction(&mut self, opcode: u16) -> Result<(), RomError> {
+        let register = self.register()?;
+        self.emit_instruction(opcode | u16::from(register) << 8);
+
+        Ok(())
+    }
+
+    // save and load, XO-CHIP's `save vx - vy` form isn't supported
+    fn register_range_instruction(&mut self, opcode: u16) -> Result<(), RomError> {
+        self.register_instruction(opcode)?;
+        if self.peek() == Some("-") {
+            return Err(RomError::UnsupportedOctoSource("-".to_string()));
+        }
+
+        Ok(())
+    }
+
+    fn operand(&mut self) -> Result<Operand, RomError> {
+        let token = self.token()?;
+        if let Some(register) = self.alias_or_register(token) {
+            return Ok(Operand::Register(register));
+        }
+
+        let value = self.number(token).ok_or_else(|| self.error(&format!("expected a register or number, found {token:?}")))?;
+        Ok(Operand::Byte(self.to_byte(value)?))
+    }
+
+    fn register(&mut self) -> Result<u8, RomError> {
+        let token = self.token()?;
+        self.alias_or_register(token).ok_or_else(|| self.error(&format!("expected a register, found {token:?}")))
+    }
+
+    fn alias_or_register(&self, token: &str) -> Option<u8> {
+        if let Some(register) = self.aliases.get(token) {
+            return Some(*register);
+        }
+
+        let digit = token.strip_prefix('v').or_else(|| token.strip_prefix('V'))?;
+        if digit.len() != 1 {
+            return None;
+        }
+        u8::from_str_radix(digit, 16).ok()
+    }
+
+    fn value(&mut self) -> Result<i32, RomError> {
+        let token = self.token()?;
+        self.number(token).ok_or_else(|| self.error(&format!("expected a number, found {token:?}")))
+    }
+
+    fn byte(&mut self) -> Result<u8, RomError> {
+        let value = self.value()?;
+        self.to_byte(value)
+    }
+
+    fn to_byte(&self, value: i32) -> Result<u8, RomError> {
+        match value {
+            -128..=255 => Ok(value as u8),
+            _ => Err(self.error(&format!("{value} doesn't fit in a byte"))),
+        }
+    }
+
+    // Octo's number syntax: decimal, 0x hex and 0b binary. Constants count as numbers
+    fn number(&self, token: &str) -> Option<i32> {
+        if let Some(value) = self.constants.get(token) {
+            return Some(*value);
+        }
+
+        let (negative, digits) = match token.strip_prefix('-') {
+            Some(digits) => (true, digits),
+            None => (false, token),
+        };
+        // from_str_radix would take another sign after the prefix
+        if digits.contains(['-', '+']) {
+            return None;
+        }
+        let value = if let Some(hex) = digits.strip_prefix("0x") {
+            i32::from_str_radix(hex, 16).ok()?
+        } else if let Some(binary) = digits.strip_prefix("0b") {
+            i32::from_str_radix(binary, 2).ok()?
+        } else if digits.starts_with(|c: char| c.is_ascii_digit()) {
+            digits.parse().ok()?
+        } else {
+            return None;
+        };
+
+        if negative { value.checked_neg() } else { Some(value) }
+    }
+
+    fn token(&mut self) -> Result<&'a str, RomError> {
+        let token = self.tokens.get(self.next)
+            .ok_or_else(|| RomError::Cartridge("program ends in the middle of a statement".to_string()))?;
+        self.next += 1;
+
+        Ok(token.text)
+    }
+
+    fn peek(&self) -> Option<&'a str> {
+        self.tokens.get(self.next).map(|token| token.text)
+    }
+
+    fn expect(&mut self, expected: &str) -> Result<(), RomError> {
+        match self.token()? {
+            token if token == expected => Ok(()),
+            token => Err(self.error(&format!("expected {expected:?}, found {token:?}"))),
+        }
+    }
+
+    // Address the next byte is assembled to
+    fn address(&self) -> Result<u16, RomError> {
+        let address = self.load_address as usize + self.position;
+        if address >= MEMORY_SIZE {
+            return Err(self.error("program doesn't fit in memory"));
+        }
+
+        Ok(address as u16)
+    }
+
+    fn emit(&mut self, bytes: &[u8]) {
+        let end = self.position + bytes.len();
+        if self.program.len() < end {
+            self.program.resize(end, 0);
+        }
+        self.program[self.position..end].copy_from_slice(bytes);
+        self.position = end;
+    }
+
+    fn emit_instruction(&mut self, instruction: u16) {
+        self.emit(&instruction.to_be_bytes());
+    }
+
+    // A jump whose target gets patched in later, returns its position
+    fn emit_jump(&mut self) -> usize {
+        let position = self.position;
+        self.emit_instruction(0x1000);
+
+        position
+    }
+
+    fn patch(&mut self, position: usize, address: u16) {
+        self.program[position] |= (address >> 8) as u8 & 0xF;
+        self.program[position + 1] |= address as u8;
+    }
+
+    fn error(&self, message: &str) -> RomError {
+        let line = self.tokens.get(self.next.saturating_sub(1)).map_or(0, |token| token.line);
+        RomError::Cartridge(format!("line {line}: {message}"))
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cpu::Cpu;
+    use crate::headless_screen::HeadlessScreen;
+    use crate::null_sound_device::NullSoundDevice;
+    use crate::rom::Rom;
+
+    fn words(program: &[u8]) -> Vec<u16> {
+        program.chunks(2).map(|pair| u16::from_be_bytes([pair[0], pair[1]])).collect()
+    }
+
+    #[test]
+    fn statements() {
+        let program = assemble("
+            : main
+              clear
+              v0 := 5  v1 := v0  v2 += 1  v2 -= 1  v3 += v4  v3 -= v4  v3 =- v4
+              v5 |= v6  v5 &= v6  v5 ^= v6  v5 >>= v6  v5 <<= v6
+              v7 := random 0x0F  v8 := delay  v9 := key  delay := va  buzzer := vb
+              i := 0x300  i += vc  i := hex vd  bcd ve  save vf  load v0
+              sprite v1 v2 5  jump0 0x208  jump main  return ;
+        ", 0x200).unwrap();
+
+        assert_eq!(words(&program), [
+            0x00E0,
+            0x6005, 0x8100, 0x7201, 0x72FF, 0x8344, 0x8345, 0x8347,
+            0x8561, 0x8562, 0x8563, 0x8566, 0x856E,
+            0xC70F, 0xF807, 0xF90A, 0xFA15, 0xFB18,
+            0xA300, 0xFC1E, 0xFD29, 0xFE33, 0xFF55, 0xF065,
+            0xD125, 0xB208, 0x1200, 0x00EE, 0x00EE,
+        ]);
+    }
+
+    #[test]
+    fn labels_calls_and_forward_references() {
+        let program = assemble("
+            : draw  i := smile  sprite v0 v0 2  ;
+            : main  draw  jump end
+            : smile  0b01100110 0x3C
+            : end  jump end
+        ", 0x200).unwrap();
+
+        // Jump to main first, since main isn't at the start
+        assert_eq!(words(&program), [0x1208, 0xA20C, 0xD002, 0x00EE, 0x2202, 0x120E, 0x663C, 0x120E]);
+
+        let program = assemble(": main jump main", 0x600).unwrap();
+        assert_eq!(words(&program), [0x1600]);
+    }
+
+    #[test]
+    fn conditions() {
+        let program = assemble("
+            : main
+              if v1 == 3 then v0 := 1
+              if v1 != v2 then v0 := 2
+              if v1 key then v0 := 3
+              if v1 -key then v0 := 4
+              if v1 < 10 then v0 := 5
+              if v1 >= v2 then v0 := 6
+              if v1 > 10 then v0 := 7
+              if v1 <= 10 then v0 := 8
+        ", 0x200).unwrap();
+
+        assert_eq!(words(&program), [
+            0x4103, 0x6001,
+            0x5120, 0x6002,
+            0xE1A1, 0x6003,
+            0xE19E, 0x6004,
+            0x6F0A, 0x8F17, 0x4F00, 0x6005,
+            0x8F20, 0x8F17, 0x4F01, 0x6006,
+            0x6F0A, 0x8F15, 0x4F00, 0x6007,
+            0x6F0A, 0x8F15, 0x4F01, 0x6008,
+        ]);
+    }
+
+    #[test]
+    fn blocks() {
+        let program = assemble("
+            : main
+              loop
+                if v0 == 1 begin
+                  v1 := 1
+                else
+                  v1 := 2
+                end
+                v0 += 1
+                while v0 != 8
+              again
+        ", 0x200).unwrap();
+
+        assert_eq!(words(&program), [
+            0x3001, 0x1208, 0x6101, 0x120A,
+            0x6102,
+            0x7001,
+            0x4008, 0x1212,
+            0x1200,
+        ]);
+    }
+
+    #[test]
+    fn directives() {
+        let program = assemble("
+            :alias x v3
+            :const SPEED 4
+            : main
+              x += SPEED
+              :unpack 0xA data
+              :next target v0 := 0
+              :byte 7
+            :org 0x210
+            : data 1 2
+        ", 0x200).unwrap();
+
+        assert_eq!(program[..10], [0x73, 0x04, 0x60, 0xA2, 0x61, 0x10, 0x60, 0x00, 0x07, 0x00]);
+        assert_eq!(program[0x10..], [1, 2]);
+    }
+
+    #[test]
+    fn errors() {
+        assert!(matches!(assemble(": main hires", 0x200), Err(RomError::UnsupportedOctoSource(token)) if token == "hires"));
+        assert!(matches!(assemble(": main save v2 - v4", 0x200), Err(RomError::UnsupportedOctoSource(_))));
+        assert!(matches!(assemble(": main\n\n  jump nowhere", 0x200), Err(RomError::Cartridge(e)) if e == "line 3: undefined label \"nowhere\""));
+        assert!(matches!(assemble(": main loop v0 += 1", 0x200), Err(RomError::Cartridge(_))));
+        assert!(matches!(assemble(": main v0 := 256", 0x200), Err(RomError::Cartridge(_))));
+        assert!(matches!(assemble(": main v0 := --2147483648", 0x200), Err(RomError::Cartridge(_))));
+        assert!(matches!(assemble(": main v0 := -0x-80000000", 0x200), Err(RomError::Cartridge(_))));
+        assert!(matches!(assemble("clear", 0x200), Err(RomError::Cartridge(_))));
+    }
+
+    #[test]
+    fn assembled_program_runs() {
+        let source = "
+            :alias counter v0
+            :alias sum v1
+            :const LIMIT 10
+
+            : add-counter  sum += counter ;
+
+            : main
+              loop
+                counter += 1
+                add-counter
+                while counter < LIMIT
+              again
+              if sum >= 55 begin v2 := 1 else v2 := 2 end
+              i := result  save v2
+            : done  jump done
+            : result
+        ";
+        let mut cpu = Cpu::new();
+        cpu.load(&Rom::from_bytes(assemble(source, 0x200).unwrap(), 0x200).unwrap()).unwrap();
+
+        let mut screen = HeadlessScreen::new();
+        let mut audio = NullSoundDevice::new();
+        let mut last_key = 255;
+        for _ in 0..200 {
+            cpu.step(&mut screen, &mut audio, &[0; 16], &mut last_key).unwrap();
+        }
+
+        let result = cpu.i as usize;
+        assert_eq!(cpu.ram[result..result + 3], [10, 55, 1]);
+    }
+}
@@ -0,0 +1,244 @@
+use std::fmt;
+use std::ops::RangeInclusive;
+
+use crate::capture::{DEFAULT_SCALE, MAX_SCALE};
+use crate::constants::MEMORY_SIZE;
+use crate::font::{FontSet, DEFAULT_FONT_ADDRESS, FONT_SIZE};
+use crate::instructions::Instructions;
+use crate::rom::DEFAULT_LOAD_ADDRESS;
+use crate::scaling::{Filter, Scaling};
+use crate::tone::{ToneSettings, Waveform, MIN_FREQUENCY, MAX_FREQUENCY};
+use crate::tracer::TraceFilter;
+use crate::wav_recorder::{DEFAULT_SAMPLE_RATE, MIN_SAMPLE_RATE, MAX_SAMPLE_RATE};
+
+pub const USAGE: &str = "Usage: chip8 [--frontend sdl|tui] [--font vip|dream6800|eti660|schip] [--font-address 0x050] [--load-address 0x200] [--entry name] [--tone-freq 440] [--waveform square|sine|triangle|noise] [--volume 0.25] [--record-audio out.wav] [--record-rate 44100] [--screenshot out.png] [--record-gif out.gif] [--capture-scale 8] [--scaling fit|integer] [--filter none|smooth|scanlines|crt] [--fullscreen] [--renderer name] [--trace] [--trace-file trace.log] [--trace-ring 64] [--trace-range 0x200-0x2FF] [--trace-instructions DrwVxVyNib,Call] <path to chip8 file, zip, gzip or - for stdin>";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Frontend {
+    Sdl,
+    Tui,
+}
+
+#[derive(Debug)]
+pub struct Options {
+    pub rom_path: String,
+    pub frontend: Frontend,
+    pub font: FontSet,
+    pub font_address: u16,
+    pub load_address: u16,
+    // File to load from a zip archive holding several ROMs
+    pub entry: Option<String>,
+    pub tone: ToneSettings,
+    // WAV file the buzzer is recorded to, and its sample rate
+    pub record_audio: Option<String>,
+    pub record_sample_rate: u32,
+    // PNG written when the emulator quits, GIF recorded from the start
+    pub screenshot: Option<String>,
+    pub record_gif: Option<String>,
+    // Image pixels per CHIP-8 pixel in screenshots and recordings
+    pub capture_scale: usize,
+    pub scaling: Scaling,
+    pub filter: Filter,
+    pub fullscreen: bool,
+    // SDL render driver to use instead of SDL's own pick
+    pub renderer: Option<String>,
+    // Log executed instructions, to trace_file or stderr
+    pub trace: bool,
+    pub trace_file: Option<String>,
+    // Keep only this many instructions and log them when the CPU fails
+    pub trace_ring: Option<usize>,
+    pub trace_filter: TraceFilter,
+}
+
+#[derive(Debug)]
+pub enum OptionsError {
+    MissingRom,
+    MissingValue(String),
+    InvalidValue { flag: String, value: String },
+    UnknownFlag(String),
+}
+
+impl fmt::Display for OptionsError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            OptionsError::MissingRom => write!(f, "No ROM path given"),
+            OptionsError::MissingValue(flag) => write!(f, "Missing value for {flag}"),
+            OptionsError::InvalidValue { flag, value } => write!(f, "Invalid value {value:?} for {flag}"),
+            OptionsError::UnknownFlag(flag) => write!(f, "Unknown option {flag}"),
+        }
+    }
+}
+
+impl Options {
+    // Parses command line arguments, without the program name
+    pub fn parse<I: IntoIterator<Item = String>>(args: I) -> Result<Options, OptionsError> {
+        let mut args = args.into_iter();
+        let mut rom_path = None;
+        let mut frontend = Frontend::Sdl;
+        let mut font = FontSet::default();
+        let mut font_address = DEFAULT_FONT_ADDRESS;
+        let mut load_address = DEFAULT_LOAD_ADDRESS;
+        let mut entry = None;
+        let mut tone = ToneSettings::default();
+        let mut record_audio = None;
+        let mut record_sample_rate = DEFAULT_SAMPLE_RATE;
+        let mut screenshot = None;
+        let mut record_gif = None;
+        let mut capture_scale = DEFAULT_SCALE;
+        let mut scaling = Scaling::default();
+        let mut filter = Filter::default();
+        let mut fullscreen = false;
+        let mut renderer = None;
+        let mut trace = false;
+        let mut trace_file = None;
+        let mut trace_ring = None;
+        let mut trace_filter = TraceFilter::default();
+
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--frontend" => {
+                    let value = args.next().ok_or_else(|| OptionsError::MissingValue(arg.clone()))?;
+                    frontend = match value.as_str() {
+                        "sdl" => Frontend::Sdl,
+                        "tui" => Frontend::Tui,
+                        _ => return Err(OptionsError::InvalidValue { flag: arg, value }),
+                    };
+                },
+                "--font" => {
+                    let value = args.next().ok_or_else(|| OptionsError::MissingValue(arg.clone()))?;
+                    font = FontSet::from_name(&value).ok_or(OptionsError::InvalidValue { flag: arg, value })?;
+                },
+                "--font-address" => {
+                    let value = args.next().ok_or_else(|| OptionsError::MissingValue(arg.clone()))?;
+                    font_address = parse_address(&value)
+                        .filter(|address| *address as usize + FONT_SIZE as usize <= MEMORY_SIZE)
+                        .ok_or(OptionsError::InvalidValue { flag: arg, value })?;
+                },
+                "--load-address" => {
+                    let value = args.next().ok_or_else(|| OptionsError::MissingValue(arg.clone()))?;
+                    load_address = parse_address(&value).ok_or(OptionsError::InvalidValue { flag: arg, value })?;
+                },
+                "--entry" => {
+                    entry = Some(args.next().ok_or_else(|| OptionsError::MissingValue(arg.clone()))?);
+                },
+                "--tone-freq" => {
+                    let value = args.next().ok_or_else(|| OptionsError::MissingValue(arg.clone()))?;
+                    tone.frequency = value.parse().ok()
+                        .filter(|frequency| (MIN_FREQUENCY..=MAX_FREQUENCY).contains(frequency))
+                        .ok_or(OptionsError::InvalidValue { flag: arg, value })?;
+                },
+                "--waveform" => {
+                    let value = args.next().ok_or_else(|| OptionsError::MissingValue(arg.clone()))?;
+                    tone.waveform = Waveform::from_name(&value).ok_or(OptionsError::InvalidValue { flag: arg, value })?;
+                },
+                "--volume" => {
+                    let value = args.next().ok_or_else(|| OptionsError::MissingValue(arg.clone()))?;
+                    tone.volume = value.parse().ok()
+                        .filter(|volume| (0.0..=1.0).contains(volume))
+                        .ok_or(OptionsError::InvalidValue { flag: arg, value })?;
+                },
+                "--record-audio" => {
+                    record_audio = Some(args.next().ok_or_else(|| OptionsError::MissingValue(arg.clone()))?);
+                },
+                "--record-rate" => {
+                    let value = args.next().ok_or_else(|| OptionsError::MissingValue(arg.clone()))?;
+                    record_sample_rate = value.parse().ok()
+                        .filter(|rate| (MIN_SAMPLE_RATE..=MAX_SAMPLE_RATE).contains(rate))
+                        .ok_or(OptionsError::InvalidValue { flag: arg, value })?;
+                },
+                "--screenshot" => {
+                    screenshot = Some(args.next().ok_or_else(|| OptionsError::MissingValue(arg.clone()))?);
+                },
+                "--record-gif" => {
+                    record_gif = Some(args.next().ok_or_else(|| OptionsError::MissingValue(arg.clone()))?);
+                },
+                "--capture-scale" => {
+                    let value = args.next().ok_or_else(|| OptionsError::MissingValue(arg.clone()))?;
+                    capture_scale = value.parse().ok()
+                        .filter(|scale| (1..=MAX_SCALE).contains(scale))
+                        .ok_or(OptionsError::InvalidValue { flag: arg, value })?;
+                },
+                "--scaling" => {
+                    let value = args.next().ok_or_else(|| OptionsError::MissingValue(arg.clone()))?;
+                    scaling = Scaling::from_name(&value).ok_or(OptionsError::InvalidValue { flag: arg, value })?;
+                },
+                "--filter" => {
+                    let value = args.next().ok_or_else(|| OptionsError::MissingValue(arg.clone()))?;
+                    filter = Filter::from_name(&value).ok_or(OptionsError::InvalidValue { flag: arg, value })?;
+                },
+                "--fullscreen" => fullscreen = true,
+                "--renderer" => {
+                    renderer = Some(args.next().ok_or_else(|| OptionsError::MissingValue(arg.clone()))?);
+                },
+                "--trace" => trace = true,
+                "--trace-file" => {
+                    trace_file = Some(args.next().ok_or_else(|| OptionsError::MissingValue(arg.clone()))?);
+                    trace = true;
+                },
+                "--trace-ring" => {
+                    let value = args.next().ok_or_else(|| OptionsError::MissingValue(arg.clone()))?;
+                    trace_ring = Some(value.parse().ok()
+                        .filter(|size| *size > 0)
+                        .ok_or(OptionsError::InvalidValue { flag: arg, value })?);
+                    trace = true;
+                },
+                "--trace-range" => {
+                    let value = args.next().ok_or_else(|| OptionsError::MissingValue(arg.clone()))?;
+                    trace_filter.addresses = Some(parse_address_range(&value).ok_or(OptionsError::InvalidValue { flag: arg, value })?);
+                    trace = true;
+                },
+                "--trace-instructions" => {
+                    let value = args.next().ok_or_else(|| OptionsError::MissingValue(arg.clone()))?;
+                    let instructions: Option<Vec<Instructions>> = value.split(',')
+                        .map(str::trim)
+                        .filter(|name| !name.is_empty())
+                        .map(Instructions::from_name)
+                        .collect();
+                    trace_filter.instructions = instructions.ok_or(OptionsError::InvalidValue { flag: arg, value })?;
+                    trace = true;
+                },
+                flag if flag.starts_with("--") => return Err(OptionsError::UnknownFlag(arg)),
+                _ => rom_path = Some(arg),
+            }
+        }
+
+        Ok(Options {
+            rom_path: rom_path.ok_or(OptionsError::MissingRom)?,
+            frontend,
+            font,
+            font_address,
+            load_address,
+            entry,
+            tone,
+            record_audio,
+            record_sample_rate,
+            screenshot,
+            record_gif,
+            capture_scale,
+            scaling,
+            filter,
+            fullscreen,
+            renderer,
+            trace,
+            trace_file,
+            trace_ring,
+            trace_filter,
+        })
+    }
+}
+
+// Accepts hex with a 0x prefix or plain decimal
+fn parse_address(value: &str) -> Option<u16> {
+    match value.strip_prefix("0x").or_else(|| value.strip_prefix("0X")) {
+        Some(hex) => u16::from_str_radix(hex, 16).ok(),
+        None => value.parse().ok(),
+    }
+}
+
+// Two addresses separated by a dash, both included
+fn parse_address_range(value: &str) -> Option<RangeInclusive<u16>> {
+    let (start, end) = value.split_once('-')?;
+    let (start, end) = (parse_address(start.trim())?, parse_address(end.trim())?);
+
+    (start <= end).then_some(start..=end)
+}
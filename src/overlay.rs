@@ -0,0 +1,175 @@
+// A software drawn panel of 0xAARRGGBB texels with a tiny built-in font, frontends show it on top
+// of the game screen for menus and debugging views
+
+pub const GLYPH_WIDTH: usize = 3;
+pub const GLYPH_HEIGHT: usize = 5;
+// Glyphs are spaced by one texel horizontally and vertically
+pub const CHAR_WIDTH: usize = GLYPH_WIDTH + 1;
+pub const LINE_HEIGHT: usize = GLYPH_HEIGHT + 1;
+
+pub struct Overlay {
+    width: usize,
+    height: usize,
+    texels: Vec<u32>,
+}
+
+impl Overlay {
+    pub fn new(width: usize, height: usize) -> Overlay {
+        Overlay {
+            width,
+            height,
+            texels: vec![0; width * height],
+        }
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn texels(&self) -> &[u32] {
+        &self.texels
+    }
+
+    // How many characters fit on one line
+    pub fn columns(&self) -> usize {
+        self.width / CHAR_WIDTH
+    }
+
+    // How many lines of text fit on the panel
+    pub fn rows(&self) -> usize {
+        self.height / LINE_HEIGHT
+    }
+
+    pub fn clear(&mut self, color: u32) {
+        self.texels.fill(color);
+    }
+
+    // Rectangles are clipped to the panel
+    pub fn fill_rect(&mut self, x: usize, y: usize, width: usize, height: usize, color: u32) {
+        for row in y..(y + height).min(self.height) {
+            let start = row * self.width;
+            self.texels[start + x.min(self.width)..start + (x + width).min(self.width)].fill(color);
+        }
+    }
+
+    // Draws text starting at character cell (column, row), anything past the right edge is cut off
+    pub fn draw_text(&mut self, column: usize, row: usize, text: &str, color: u32) {
+        let y = row * LINE_HEIGHT;
+
+        for (i, c) in text.chars().enumerate() {
+            let x = (column + i) * CHAR_WIDTH;
+            if x + GLYPH_WIDTH > self.width || y + GLYPH_HEIGHT > self.height {
+                break;
+            }
+
+            for (dy, bits) in glyph(c).iter().enumerate() {
+                for dx in 0..GLYPH_WIDTH {
+                    if bits & (0b100 >> dx) != 0 {
+                        self.texels[(y + dy) * self.width + x + dx] = color;
+                    }
+                }
+            }
+        }
+    }
+}
+
+// 3x5 glyphs, one byte per row with the leftmost texel in bit 2. Letters are upper case only
+fn glyph(c: char) -> [u8; GLYPH_HEIGHT] {
+    match c.to_ascii_uppercase() {
+        'A' => [0b010, 0b101, 0b111, 0b101, 0b101],
+        'B' => [0b110, 0b101, 0b110, 0b101, 0b110],
+        'C' => [0b011, 0b100, 0b100, 0b100, 0b011],
+        'D' => [0b110, 0b101, 0b101, 0b101, 0b110],
+        'E' => [0b111, 0b100, 0b110, 0b100, 0b111],
+        'F' => [0b111, 0b100, 0b110, 0b100, 0b100],
+        'G' => [0b011, 0b100, 0b101, 0b101, 0b011],
+        'H' => [0b101, 0b101, 0b111, 0b101, 0b101],
+        'I' => [0b111, 0b010, 0b010, 0b010, 0b111],
+        'J' => [0b001, 0b001, 0b001, 0b101, 0b010],
+        'K' => [0b101, 0b101, 0b110, 0b101, 0b101],
+        'L' => [0b100, 0b100, 0b100, 0b100, 0b111],
+        'M' => [0b101, 0b111, 0b111, 0b101, 0b101],
+        'N' => [0b110, 0b101, 0b101, 0b101, 0b101],
+        'O' => [0b010, 0b101, 0b101, 0b101, 0b010],
+        'P' => [0b110, 0b101, 0b110, 0b100, 0b100],
+        'Q' => [0b010, 0b101, 0b101, 0b110, 0b011],
+        'R' => [0b110, 0b101, 0b110, 0b101, 0b101],
+        'S' => [0b011, 0b100, 0b010, 0b001, 0b110],
+        'T' => [0b111, 0b010, 0b010, 0b010, 0b010],
+        'U' => [0b101, 0b101, 0b101, 0b101, 0b111],
+        'V' => [0b101, 0b101, 0b101, 0b101, 0b010],
+        'W' => [0b101, 0b101, 0b111, 0b111, 0b101],
+        'X' => [0b101, 0b101, 0b010, 0b101, 0b101],
+        'Y' => [0b101, 0b101, 0b010, 0b010, 0b010],
+        'Z' => [0b111, 0b001, 0b010, 0b100, 0b111],
+        '0' => [0b111, 0b101, 0b101, 0b101, 0b111],
+        '1' => [0b010, 0b110, 0b010, 0b010, 0b111],
+        '2' => [0b110, 0b001, 0b010, 0b100, 0b111],
+        '3' => [0b110, 0b001, 0b010, 0b001, 0b110],
+        '4' => [0b101, 0b101, 0b111, 0b001, 0b001],
+        '5' => [0b111, 0b100, 0b110, 0b001, 0b110],
+        '6' => [0b011, 0b100, 0b111, 0b101, 0b111],
+        '7' => [0b111, 0b001, 0b010, 0b010, 0b010],
+        '8' => [0b111, 0b101, 0b111, 0b101, 0b111],
+        '9' => [0b111, 0b101, 0b111, 0b001, 0b110],
+        ' ' => [0b000, 0b000, 0b000, 0b000, 0b000],
+        '.' => [0b000, 0b000, 0b000, 0b000, 0b010],
+        ',' => [0b000, 0b000, 0b000, 0b010, 0b100],
+        ':' => [0b000, 0b010, 0b000, 0b010, 0b000],
+        '-' => [0b000, 0b000, 0b111, 0b000, 0b000],
+        '_' => [0b000, 0b000, 0b000, 0b000, 0b111],
+        '+' => [0b000, 0b010, 0b111, 0b010, 0b000],
+        '=' => [0b000, 0b111, 0b000, 0b111, 0b000],
+        '/' => [0b001, 0b001, 0b010, 0b100, 0b100],
+        '<' => [0b001, 0b010, 0b100, 0b010, 0b001],
+        '>' => [0b100, 0b010, 0b001, 0b010, 0b100],
+        '(' => [0b001, 0b010, 0b010, 0b010, 0b001],
+        ')' => [0b100, 0b010, 0b010, 0b010, 0b100],
+        '[' => [0b110, 0b100, 0b100, 0b100, 0b110],
+        ']' => [0b011, 0b001, 0b001, 0b001, 0b011],
+        '!' => [0b010, 0b010, 0b010, 0b000, 0b010],
+        '#' => [0b101, 0b111, 0b101, 0b111, 0b101],
+        '*' => [0b000, 0b101, 0b010, 0b101, 0b000],
+        _ => [0b110, 0b001, 0b010, 0b000, 0b010], // '?'
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn draw_text_renders_glyphs_in_cells() {
+        let mut overlay = Overlay::new(16, 12);
+        overlay.draw_text(1, 1, "t", 9);
+
+        // The T starts one cell to the right and one line down
+        let top = LINE_HEIGHT * overlay.width() + CHAR_WIDTH;
+        let stem = top + overlay.width();
+        assert_eq!(overlay.texels()[top - 1..top + 4], [0, 9, 9, 9, 0]);
+        assert_eq!(overlay.texels()[stem..stem + 3], [0, 9, 0]);
+    }
+
+    #[test]
+    fn text_past_the_edge_is_cut_off() {
+        let mut overlay = Overlay::new(8, 6);
+        overlay.draw_text(0, 0, "ii", 1);
+        overlay.draw_text(1, 0, "iii", 2);
+
+        assert_eq!(overlay.columns(), 2);
+        assert_eq!(overlay.texels().iter().filter(|t| **t == 2).count(), 9);
+    }
+
+    #[test]
+    fn fill_rect_is_clipped() {
+        let mut overlay = Overlay::new(4, 4);
+        overlay.fill_rect(2, 2, 10, 10, 7);
+
+        assert_eq!(overlay.texels().iter().filter(|t| **t == 7).count(), 4);
+    }
+}
@@ -0,0 +1,45 @@
+// Colours used to render the framebuffer, indexed by the plane bits of a pixel. Index 0 is the
+// background and index 1 the foreground (plane 1), indices 2 and 3 are only reached by XO-CHIP's
+// second plane
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Palette {
+    pub name: &'static str,
+    pub colors: [u32; 4],
+    pub grid: u32,
+}
+
+impl Palette {
+    pub fn background(&self) -> u32 {
+        self.colors[0]
+    }
+
+    pub fn color(&self, planes: u8) -> u32 {
+        self.colors[(planes & 0x3) as usize]
+    }
+}
+
+pub static CLASSIC_GREEN: Palette = Palette {
+    name: "classic green",
+    colors: [0x00000000, 0x0000ff00, 0x00007f00, 0x00bfffbf],
+    grid: 0x00202020,
+};
+
+pub static AMBER: Palette = Palette {
+    name: "amber",
+    colors: [0x001a0f00, 0x00ffb000, 0x007f5800, 0x00ffd870],
+    grid: 0x00302000,
+};
+
+pub static LCD: Palette = Palette {
+    name: "lcd",
+    colors: [0x009bbc0f, 0x000f380f, 0x008bac0f, 0x00306230],
+    grid: 0x008bac0f,
+};
+
+pub static HIGH_CONTRAST: Palette = Palette {
+    name: "high contrast",
+    colors: [0x00000000, 0x00ffffff, 0x00ffff00, 0x0000ffff],
+    grid: 0x00404040,
+};
+
+pub static THEMES: [&Palette; 4] = [&CLASSIC_GREEN, &AMBER, &LCD, &HIGH_CONTRAST];
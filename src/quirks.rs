@@ -0,0 +1,33 @@
+// Behaviours that differ between CHIP-8 interpreters, named after Octo's compatibility options.
+// The defaults are what this emulator has always done
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Quirks {
+    // 8XY6 and 8XYE shift Vx in place instead of shifting Vy into Vx
+    pub shift: bool,
+    // FX55 and FX65 leave I alone instead of moving it past the last register
+    pub load_store: bool,
+    // 8XYx writes VF before the result, so the result wins when Vx is VF
+    pub vf_order: bool,
+    // Sprites are cut off at the screen edges instead of wrapping around
+    pub clip: bool,
+    // BXNN jumps to XNN + VX instead of BNNN jumping to NNN + V0
+    pub jump: bool,
+    // 8XY1, 8XY2 and 8XY3 reset VF to 0
+    pub logic: bool,
+    // DXYN waits for the next frame before the program continues
+    pub vblank: bool,
+}
+
+impl Default for Quirks {
+    fn default() -> Quirks {
+        Quirks {
+            shift: true,
+            load_store: true,
+            vf_order: false,
+            clip: false,
+            jump: false,
+            logic: false,
+            vblank: false,
+        }
+    }
+}
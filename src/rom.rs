@@ -0,0 +1,160 @@
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::io::{self, Read};
+use std::path::Path;
+
+use crate::archive;
+use crate::cartridge::{self, Cartridge, CartridgeOptions};
+use crate::constants::MEMORY_SIZE;
+
+// Everything below is reserved for the interpreter (font data, interpreter code on real hardware etc.)
+pub const DEFAULT_LOAD_ADDRESS: u16 = 0x200;
+pub const ETI_660_LOAD_ADDRESS: u16 = 0x600;
+
+#[derive(Debug)]
+pub enum RomError {
+    Io(io::Error),
+    Empty,
+    TooLarge { size: usize, max: usize },
+    InvalidLoadAddress(u16),
+    Archive(String),
+    EntryNotFound(String),
+    AmbiguousEntry(Vec<String>),
+    Cartridge(String),
+    UnsupportedOctoSource(String),
+}
+
+impl fmt::Display for RomError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RomError::Io(e) => write!(f, "{e}"),
+            RomError::Empty => write!(f, "ROM is empty"),
+            RomError::TooLarge { size, max } => write!(f, "ROM is {size} bytes, at most {max} fit in memory"),
+            RomError::InvalidLoadAddress(address) => write!(f, "Load address {address:#X} is outside of memory"),
+            RomError::Archive(e) => write!(f, "Invalid archive: {e}"),
+            RomError::EntryNotFound(entry) => write!(f, "Archive has no entry {entry:?}"),
+            RomError::AmbiguousEntry(entries) => write!(f, "Archive has several ROMs, pick one with --entry: {}", entries.join(", ")),
+            RomError::Cartridge(e) => write!(f, "Invalid Octo cartridge: {e}"),
+            RomError::UnsupportedOctoSource(token) => write!(f, "Octo cartridge uses {token:?}, only CHIP-8 programs without macros can be assembled"),
+        }
+    }
+}
+
+impl Error for RomError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            RomError::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for RomError {
+    fn from(e: io::Error) -> Self {
+        RomError::Io(e)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Rom {
+    data: Vec<u8>,
+    load_address: u16,
+    // Settings that came with the program when it was loaded from an Octo cartridge
+    cartridge_options: Option<CartridgeOptions>,
+}
+
+impl Rom {
+    pub fn from_bytes(data: Vec<u8>, load_address: u16) -> Result<Rom, RomError> {
+        if load_address as usize >= MEMORY_SIZE {
+            return Err(RomError::InvalidLoadAddress(load_address));
+        }
+
+        if data.is_empty() {
+            return Err(RomError::Empty);
+        }
+
+        let max = MEMORY_SIZE - load_address as usize;
+        if data.len() > max {
+            return Err(RomError::TooLarge { size: data.len(), max });
+        }
+
+        Ok(Rom { data, load_address, cartridge_options: None })
+    }
+
+    pub fn from_path<P: AsRef<Path>>(path: P, load_address: u16) -> Result<Rom, RomError> {
+        Rom::open(path, load_address, None)
+    }
+
+    // Like from_path, but "-" reads the ROM from stdin, zip or gzip archives are unpacked first
+    // and Octo cartridges are decoded.
+    // `entry` picks the file to load out of a zip with more than one ROM in it
+    pub fn open<P: AsRef<Path>>(path: P, load_address: u16, entry: Option<&str>) -> Result<Rom, RomError> {
+        let path = path.as_ref();
+        let data = if path == Path::new("-") {
+            let mut data = vec![];
+            io::stdin().lock().read_to_end(&mut data)?;
+            data
+        } else {
+            fs::read(path)?
+        };
+
+        let data = archive::unpack(data, entry)?;
+        if cartridge::is_cartridge(&data) {
+            let cartridge = Cartridge::parse(&data, load_address)?;
+            let mut rom = Rom::from_bytes(cartridge.program, load_address)?;
+            rom.cartridge_options = Some(cartridge.options);
+
+            return Ok(rom);
+        }
+
+        Rom::from_bytes(data, load_address)
+    }
+
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+
+    pub fn load_address(&self) -> u16 {
+        self.load_address
+    }
+
+    pub fn cartridge_options(&self) -> Option<&CartridgeOptions> {
+        self.cartridge_options.as_ref()
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn from_bytes_fills_memory_up_to_the_end() {
+        let rom = Rom::from_bytes(vec![0xAB; MEMORY_SIZE - 0x200], DEFAULT_LOAD_ADDRESS).unwrap();
+        assert_eq!(rom.data().len(), 3584);
+        assert_eq!(rom.load_address(), 0x200);
+    }
+
+    #[test]
+    fn from_bytes_rejects_too_large() {
+        let result = Rom::from_bytes(vec![0; MEMORY_SIZE - 0x600 + 1], ETI_660_LOAD_ADDRESS);
+        assert!(matches!(result, Err(RomError::TooLarge { size: 2561, max: 2560 })));
+    }
+
+    #[test]
+    fn from_bytes_rejects_empty() {
+        assert!(matches!(Rom::from_bytes(vec![], DEFAULT_LOAD_ADDRESS), Err(RomError::Empty)));
+    }
+
+    #[test]
+    fn from_bytes_rejects_load_address_outside_memory() {
+        assert!(matches!(Rom::from_bytes(vec![0], 0x1000), Err(RomError::InvalidLoadAddress(0x1000))));
+    }
+
+    #[test]
+    fn from_path_reports_missing_file() {
+        let result = Rom::from_path("this/rom/does/not/exist.ch8", DEFAULT_LOAD_ADDRESS);
+        assert!(matches!(result, Err(RomError::Io(e)) if e.kind() == io::ErrorKind::NotFound));
+    }
+}
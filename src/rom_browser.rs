@@ -0,0 +1,218 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::overlay::{Overlay, LINE_HEIGHT};
+
+const BACKGROUND_COLOR: u32 = 0xE0000000;
+const TEXT_COLOR: u32 = 0xFFC0C0C0;
+const DIRECTORY_COLOR: u32 = 0xFF80A0FF;
+const SELECTION_COLOR: u32 = 0xFF404040;
+const TITLE_COLOR: u32 = 0xFFFFFFFF;
+const ERROR_COLOR: u32 = 0xFFFF6060;
+
+// Lists a directory to pick a ROM from, subdirectories can be entered and ".." goes up
+pub struct RomBrowser {
+    directory: PathBuf,
+    // The parent directory (if any) first, then directories, then files, each sorted by name
+    entries: Vec<Entry>,
+    selected: usize,
+    scroll: usize,
+    // Why the last picked file couldn't be loaded, shown on the bottom line until the next key
+    error: Option<String>,
+}
+
+struct Entry {
+    name: String,
+    path: PathBuf,
+    is_directory: bool,
+}
+
+impl RomBrowser {
+    pub fn open<P: AsRef<Path>>(directory: P) -> io::Result<RomBrowser> {
+        let mut browser = RomBrowser {
+            directory: PathBuf::new(),
+            entries: vec![],
+            selected: 0,
+            scroll: 0,
+            error: None,
+        };
+        browser.change_directory(directory.as_ref())?;
+
+        Ok(browser)
+    }
+
+    pub fn directory(&self) -> &Path {
+        &self.directory
+    }
+
+    pub fn show_error(&mut self, error: String) {
+        self.error = Some(error);
+    }
+
+    pub fn up(&mut self) {
+        self.error = None;
+        self.selected = self.selected.saturating_sub(1);
+    }
+
+    pub fn down(&mut self) {
+        self.error = None;
+        if self.selected + 1 < self.entries.len() {
+            self.selected += 1;
+        }
+    }
+
+    // Enters the selected directory, or returns the selected file to load
+    pub fn activate(&mut self) -> io::Result<Option<PathBuf>> {
+        self.error = None;
+        let Some(entry) = self.entries.get(self.selected) else {
+            return Ok(None);
+        };
+
+        if entry.is_directory {
+            let path = entry.path.clone();
+            self.change_directory(&path)?;
+            Ok(None)
+        } else {
+            Ok(Some(entry.path.clone()))
+        }
+    }
+
+    pub fn parent(&mut self) -> io::Result<()> {
+        self.error = None;
+        match self.directory.parent().map(Path::to_path_buf) {
+            Some(parent) => self.change_directory(&parent),
+            None => Ok(()),
+        }
+    }
+
+    pub fn render(&mut self, overlay: &mut Overlay) {
+        overlay.clear(BACKGROUND_COLOR);
+        overlay.draw_text(0, 0, &self.directory.display().to_string(), TITLE_COLOR);
+
+        // Keep the selection on screen below the title line and above the error, if any
+        let visible = overlay.rows().saturating_sub(1 + usize::from(self.error.is_some())).max(1);
+        if self.selected < self.scroll {
+            self.scroll = self.selected;
+        } else if self.selected >= self.scroll + visible {
+            self.scroll = self.selected + 1 - visible;
+        }
+
+        for (row, (index, entry)) in self.entries.iter().enumerate().skip(self.scroll).take(visible).enumerate() {
+            let row = row + 1;
+            if index == self.selected {
+                let y = row * LINE_HEIGHT;
+                overlay.fill_rect(0, y.saturating_sub(1), overlay.width(), LINE_HEIGHT + 1, SELECTION_COLOR);
+            }
+
+            let (name, color) = if entry.is_directory {
+                (format!("{}/", entry.name), DIRECTORY_COLOR)
+            } else {
+                (entry.name.clone(), TEXT_COLOR)
+            };
+            overlay.draw_text(1, row, &name, color);
+        }
+
+        if let Some(error) = &self.error {
+            overlay.draw_text(0, overlay.rows().saturating_sub(1), error, ERROR_COLOR);
+        }
+    }
+
+    fn change_directory(&mut self, directory: &Path) -> io::Result<()> {
+        // Canonical paths make ".." and the title show real directory names
+        let directory = directory.canonicalize()?;
+
+        let mut directories = vec![];
+        let mut files = vec![];
+        for entry in fs::read_dir(&directory)? {
+            let entry = entry?;
+            let name = entry.file_name().to_string_lossy().into_owned();
+            if name.starts_with('.') {
+                continue;
+            }
+
+            let is_directory = entry.file_type()?.is_dir();
+            let list = if is_directory { &mut directories } else { &mut files };
+            list.push(Entry { name, path: entry.path(), is_directory });
+        }
+
+        directories.sort_by(|a, b| a.name.cmp(&b.name));
+        files.sort_by(|a, b| a.name.cmp(&b.name));
+
+        self.entries = directory.parent()
+            .map(|parent| Entry { name: "..".to_string(), path: parent.to_path_buf(), is_directory: true })
+            .into_iter()
+            .chain(directories)
+            .chain(files)
+            .collect();
+        self.directory = directory;
+        self.selected = 0;
+        self.scroll = 0;
+
+        Ok(())
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn names(browser: &RomBrowser) -> Vec<&str> {
+        browser.entries.iter().map(|entry| entry.name.as_str()).collect()
+    }
+
+    #[test]
+    fn lists_directories_before_files() {
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests");
+        let browser = RomBrowser::open(&dir).unwrap();
+
+        let names = names(&browser);
+        assert_eq!(names[0], "..");
+        let position = |name| names.iter().position(|n| *n == name).unwrap();
+        assert!(position("roms") < position("conformance.rs"));
+    }
+
+    // Moves the selection down to `name`, which has to be listed
+    fn select(browser: &mut RomBrowser, name: &str) {
+        let index = names(browser).iter().position(|n| *n == name);
+        assert!(index.is_some(), "{name} is not listed");
+
+        for _ in browser.selected..index.unwrap() {
+            browser.down();
+        }
+        assert_eq!(names(browser)[browser.selected], name);
+    }
+
+    #[test]
+    fn enter_directory_and_pick_file() {
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests");
+        let mut browser = RomBrowser::open(&dir).unwrap();
+
+        select(&mut browser, "roms");
+        assert_eq!(browser.activate().unwrap(), None);
+        assert!(browser.directory().ends_with("tests/roms"));
+
+        select(&mut browser, "hex-font.ch8");
+        let picked = browser.activate().unwrap().unwrap();
+        assert!(picked.ends_with("roms/hex-font.ch8"));
+
+        browser.parent().unwrap();
+        assert!(browser.directory().ends_with("tests"));
+    }
+
+    #[test]
+    fn error_stays_until_the_next_key() {
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests");
+        let mut browser = RomBrowser::open(&dir).unwrap();
+
+        browser.show_error("Font at 0x50 overlaps the program".to_string());
+        let mut overlay = Overlay::new(128, 64);
+        browser.render(&mut overlay);
+        assert!(overlay.texels().contains(&ERROR_COLOR));
+
+        browser.down();
+        browser.render(&mut overlay);
+        assert!(!overlay.texels().contains(&ERROR_COLOR));
+    }
+}
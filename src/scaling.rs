@@ -0,0 +1,121 @@
+use crate::constants::{SCREEN_WIDTH, SCREEN_HEIGHT};
+
+// How the CHIP-8 screen is sized to the window, the rest of the window is a black border
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Scaling {
+    // As large as fits, keeping the 2:1 aspect ratio
+    #[default]
+    Fit,
+    // The largest whole multiple of the CHIP-8 resolution that fits, every pixel the same size
+    Integer,
+}
+
+impl Scaling {
+    pub const ALL: [Scaling; 2] = [Scaling::Fit, Scaling::Integer];
+
+    pub fn from_name(name: &str) -> Option<Scaling> {
+        Scaling::ALL.into_iter().find(|scaling| scaling.name() == name)
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Scaling::Fit => "fit",
+            Scaling::Integer => "integer",
+        }
+    }
+
+    pub fn next(&self) -> Scaling {
+        let index = Scaling::ALL.iter().position(|scaling| scaling == self).unwrap();
+
+        Scaling::ALL[(index + 1) % Scaling::ALL.len()]
+    }
+}
+
+// Post-processing done with plain texture blending, no shaders needed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Filter {
+    // Sharp square pixels
+    #[default]
+    None,
+    // Bilinear filtering when scaling up
+    Smooth,
+    // Darkens the lower part of every pixel row
+    Scanlines,
+    // Tints the columns of every pixel red, green and blue like an aperture grille
+    CrtMask,
+}
+
+impl Filter {
+    pub const ALL: [Filter; 4] = [Filter::None, Filter::Smooth, Filter::Scanlines, Filter::CrtMask];
+
+    pub fn from_name(name: &str) -> Option<Filter> {
+        Filter::ALL.into_iter().find(|filter| filter.name() == name)
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Filter::None => "none",
+            Filter::Smooth => "smooth",
+            Filter::Scanlines => "scanlines",
+            Filter::CrtMask => "crt",
+        }
+    }
+
+    pub fn next(&self) -> Filter {
+        let index = Filter::ALL.iter().position(|filter| filter == self).unwrap();
+
+        Filter::ALL[(index + 1) % Filter::ALL.len()]
+    }
+}
+
+// Where the screen goes in a window of `width` x `height`: x, y, width and height, centred
+pub fn viewport(width: u32, height: u32, scaling: Scaling) -> (i32, i32, u32, u32) {
+    let (screen_width, screen_height) = (SCREEN_WIDTH as u32, SCREEN_HEIGHT as u32);
+
+    let (view_width, view_height) = match scaling {
+        Scaling::Fit => {
+            if width * screen_height > height * screen_width {
+                (height * screen_width / screen_height, height)
+            } else {
+                (width, width * screen_height / screen_width)
+            }
+        },
+        Scaling::Integer => {
+            // Windows smaller than the CHIP-8 screen still get one texel per pixel
+            let scale = (width / screen_width).min(height / screen_height).max(1);
+            (screen_width * scale, screen_height * scale)
+        },
+    };
+
+    let x = (width as i32 - view_width as i32) / 2;
+    let y = (height as i32 - view_height as i32) / 2;
+
+    (x, y, view_width, view_height)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fit_letterboxes_wide_windows() {
+        assert_eq!(viewport(1920, 1080, Scaling::Fit), (0, 60, 1920, 960));
+        assert_eq!(viewport(1000, 1000, Scaling::Fit), (0, 250, 1000, 500));
+        assert_eq!(viewport(1000, 300, Scaling::Fit), (200, 0, 600, 300));
+    }
+
+    #[test]
+    fn integer_scaling_uses_whole_multiples() {
+        assert_eq!(viewport(1920, 1080, Scaling::Integer), (0, 60, 1920, 960));
+        assert_eq!(viewport(1000, 1000, Scaling::Integer), (20, 260, 960, 480));
+        assert_eq!(viewport(50, 20, Scaling::Integer), (-7, -6, 64, 32));
+    }
+
+    #[test]
+    fn names() {
+        assert_eq!(Scaling::from_name("integer"), Some(Scaling::Integer));
+        assert_eq!(Filter::from_name("crt"), Some(Filter::CrtMask));
+        assert_eq!(Filter::CrtMask.next(), Filter::None);
+    }
+}
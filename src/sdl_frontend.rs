@@ -0,0 +1,430 @@
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+
+use sdl2::event::{Event, WindowEvent};
+use sdl2::keyboard::{Keycode, Mod};
+use sdl2::render::Canvas;
+use sdl2::video::Window;
+use sdl2::VideoSubsystem;
+
+use crate::audible::Audible;
+use crate::capture::Capture;
+use crate::constants::{SCREEN_WIDTH, SCREEN_HEIGHT};
+use crate::cpu::Cpu;
+use crate::drawable::Drawable;
+use crate::font::FontAddressError;
+use crate::frame::{run_frame, wait_for_next_frame};
+use crate::frontend_error::FrontendError;
+use crate::memory_viewer::MemoryViewer;
+use crate::overlay::Overlay;
+use crate::options::Options;
+use crate::palette::{Palette, THEMES};
+use crate::rom::{Rom, RomError, DEFAULT_LOAD_ADDRESS};
+use crate::rom_browser::RomBrowser;
+use crate::sdl_screen::{SDLScreen, RenderMode, OVERLAY_WIDTH, OVERLAY_HEIGHT};
+use crate::sdl_sound_device::SDLSoundDevice;
+use crate::tone::{ToneSettings, MIN_FREQUENCY, MAX_FREQUENCY};
+use crate::wav_recorder::RecordedDevice;
+
+static RENDER_MODES: [RenderMode; 5] = [
+    RenderMode::Direct,
+    RenderMode::Phosphor { persistence: 0.5 },
+    RenderMode::Phosphor { persistence: 0.75 },
+    RenderMode::Phosphor { persistence: 0.9 },
+    RenderMode::FrameBlend,
+];
+
+// Frequency hotkeys move the tone by a semitone
+const SEMITONE: f32 = 1.059_463_1;
+const VOLUME_STEP: f32 = 0.05;
+
+// Index of the SDL render driver called `name` (e.g. "opengl", "metal", "software")
+fn find_render_driver(name: &str) -> Option<u32> {
+    sdl2::render::drivers().position(|driver| driver.name == name).map(|index| index as u32)
+}
+
+// Creates the window on the render driver asked for with --renderer, or SDL's pick without one.
+// A driver that isn't there falls back to SDL's pick, one that fails to start to software rendering
+fn create_canvas(video_subsystem: &VideoSubsystem, renderer: Option<&str>) -> Canvas<Window> {
+    let index = renderer.and_then(|name| {
+        let index = find_render_driver(name);
+        if index.is_none() {
+            let available: Vec<&str> = sdl2::render::drivers().map(|driver| driver.name).collect();
+            println!("Render driver {name} not available (available: {}), using the default", available.join(", "));
+        }
+        index
+    });
+
+    let mut builder = create_window(video_subsystem).into_canvas().present_vsync();
+    if let Some(index) = index {
+        builder = builder.index(index);
+    }
+
+    match builder.build() {
+        Ok(canvas) => canvas,
+        Err(e) => {
+            println!("Failed to create renderer: {e}, falling back to software rendering");
+            create_window(video_subsystem).into_canvas().software().build().unwrap()
+        },
+    }
+}
+
+fn create_window(video_subsystem: &VideoSubsystem) -> Window {
+    video_subsystem.window("CHIP-8", SCREEN_WIDTH as u32 * 20, SCREEN_HEIGHT as u32 * 20)
+    .position_centered()
+    .resizable()
+    .build().unwrap()
+}
+
+// `palette` is shown first when the ROM brought its own colours, F1 then cycles through the themes
+pub fn run(mut cpu: Cpu, palette: Option<Palette>, options: &Options) -> Result<(), FrontendError> {
+    let mut palettes = palettes_for(palette);
+    let sdl_context = sdl2::init().unwrap();
+    let video_subsystem = sdl_context.video().unwrap();
+    let mut canvas = create_canvas(&video_subsystem, options.renderer.as_deref());
+    canvas.clear();
+    canvas.present();
+
+    let mut theme = 0;
+    let mut render_mode = 0;
+    let texture_creator = canvas.texture_creator();
+    let mut screen = SDLScreen::new(canvas, &texture_creator, palettes[theme]);
+    screen.set_scaling(options.scaling);
+    screen.set_filter(options.filter);
+    if options.fullscreen {
+        toggle_fullscreen(&mut screen);
+    }
+    let mut tone = options.tone;
+    let mut sdl_audio_device = RecordedDevice::new(create_audio_device(&sdl_context, tone), options)?;
+    let mut capture = Capture::new(options, screen.palette())?;
+    let mut rom_directory = directory_of(Path::new(&options.rom_path));
+    
+
+    let mut event_pump = sdl_context.event_pump().unwrap();
+
+    let mut pressed_keys = [0; 16];
+    let mut last_key: u8 = 255;
+
+    let mut browser: Option<RomBrowser> = None;
+    let mut viewer: Option<MemoryViewer> = None;
+    let mut overlay = Overlay::new(OVERLAY_WIDTH, OVERLAY_HEIGHT);
+
+    let mut next_frame = Instant::now();
+
+    let mut result = Ok(());
+    'running: loop {
+        let mut rom_to_load = None;
+
+        for event in event_pump.poll_iter() {
+            match event {
+                Event::Quit { .. } => {
+                    break 'running;
+                },
+                Event::Window { win_event: WindowEvent::SizeChanged(..) | WindowEvent::Exposed, .. } => {
+                    screen.force_redraw();
+                },
+                Event::DropFile { filename, .. } => {
+                    rom_to_load = Some(PathBuf::from(filename));
+                },
+                Event::KeyDown { keycode: Some(Keycode::F1), .. } => {
+                    theme = (theme + 1) % palettes.len();
+                    screen.set_palette(palettes[theme]);
+                },
+                Event::KeyDown { keycode: Some(Keycode::F2), .. } => {
+                    screen.set_grid(!screen.grid());
+                },
+                Event::KeyDown { keycode: Some(Keycode::F3), .. } => {
+                    render_mode = (render_mode + 1) % RENDER_MODES.len();
+                    screen.set_render_mode(RENDER_MODES[render_mode]);
+                },
+                Event::KeyDown { keycode: Some(Keycode::F4), keymod, .. } => {
+                    if keymod.intersects(Mod::LSHIFTMOD | Mod::RSHIFTMOD) {
+                        screen.set_filter(screen.filter().next());
+                    } else {
+                        screen.set_scaling(screen.scaling().next());
+                    }
+                },
+                Event::KeyDown { keycode: Some(Keycode::Return), keymod, .. } if keymod.intersects(Mod::LALTMOD | Mod::RALTMOD) => {
+                    toggle_fullscreen(&mut screen);
+                },
+                Event::KeyDown { keycode: Some(Keycode::F5), keymod, .. } => {
+                    if keymod.intersects(Mod::LSHIFTMOD | Mod::RSHIFTMOD) {
+                        cpu.hard_reset(&mut screen, &mut sdl_audio_device);
+                    } else {
+                        cpu.soft_reset(&mut screen, &mut sdl_audio_device);
+                    }
+                },
+                Event::KeyDown { keycode: Some(Keycode::F7), .. } => {
+                    tone.waveform = tone.waveform.next();
+                    sdl_audio_device.set_tone(tone);
+                },
+                Event::KeyDown { keycode: Some(keycode @ (Keycode::F8 | Keycode::F9)), keymod, .. } => {
+                    let up = keycode == Keycode::F9;
+                    if keymod.intersects(Mod::LSHIFTMOD | Mod::RSHIFTMOD) {
+                        let factor = if up { SEMITONE } else { 1.0 / SEMITONE };
+                        tone.frequency = (tone.frequency * factor).clamp(MIN_FREQUENCY, MAX_FREQUENCY);
+                    } else {
+                        let step = if up { VOLUME_STEP } else { -VOLUME_STEP };
+                        tone.volume = (tone.volume + step).clamp(0.0, 1.0);
+                    }
+                    sdl_audio_device.set_tone(tone);
+                },
+                Event::KeyDown { keycode: Some(Keycode::F12), keymod, .. } => {
+                    if !keymod.intersects(Mod::LSHIFTMOD | Mod::RSHIFTMOD) {
+                        match capture.screenshot(screen.framebuffer(), screen.palette()) {
+                            Ok(path) => println!("Screenshot saved to {}", path.display()),
+                            Err(e) => println!("Failed to save screenshot: {e}"),
+                        }
+                    } else if capture.is_recording() {
+                        match capture.stop_recording() {
+                            Ok(()) => println!("Recording stopped"),
+                            Err(e) => println!("Failed to save recording: {e}"),
+                        }
+                    } else {
+                        match capture.start_recording(screen.palette()) {
+                            Ok(path) => println!("Recording to {}", path.display()),
+                            Err(e) => println!("Failed to start recording: {e}"),
+                        }
+                    }
+                },
+                Event::KeyDown { keycode: Some(Keycode::F6), .. } => {
+                    if browser.is_some() {
+                        browser = None;
+                    } else {
+                        match RomBrowser::open(&rom_directory) {
+                            Ok(opened) => {
+                                browser = Some(opened);
+                                viewer = None;
+                            },
+                            Err(e) => println!("Failed to open {}: {e}", rom_directory.display()),
+                        }
+                    }
+                },
+                Event::KeyDown { keycode: Some(Keycode::Backquote), .. } => {
+                    if viewer.is_some() {
+                        viewer = None;
+                    } else {
+                        viewer = Some(MemoryViewer::new(&cpu));
+                        browser = None;
+                    }
+                },
+                // The game is paused and doesn't see any keys while the memory viewer is open
+                Event::KeyDown { keycode: Some(keycode), .. } if viewer.is_some() => {
+                    let memory_viewer = viewer.as_mut().unwrap();
+                    match keycode {
+                        Keycode::Up => memory_viewer.up(),
+                        Keycode::Down => memory_viewer.down(),
+                        Keycode::Left => memory_viewer.left(),
+                        Keycode::Right => memory_viewer.right(),
+                        Keycode::PageUp => memory_viewer.page_up(),
+                        Keycode::PageDown => memory_viewer.page_down(),
+                        Keycode::Tab => memory_viewer.toggle_focus(),
+                        Keycode::P => memory_viewer.jump_to_pc(&cpu),
+                        Keycode::I => memory_viewer.jump_to_i(&cpu),
+                        Keycode::Escape => viewer = None,
+                        _ => {
+                            if let Some(digit) = hex_digit(keycode) {
+                                memory_viewer.edit(&mut cpu, digit);
+                            }
+                        },
+                    }
+                },
+                // The game is paused and doesn't see any keys while the browser is open
+                Event::KeyDown { keycode: Some(keycode), .. } if browser.is_some() => {
+                    let rom_browser = browser.as_mut().unwrap();
+                    let result = match keycode {
+                        Keycode::Up => {
+                            rom_browser.up();
+                            Ok(())
+                        },
+                        Keycode::Down => {
+                            rom_browser.down();
+                            Ok(())
+                        },
+                        Keycode::Backspace => rom_browser.parent(),
+                        Keycode::Return => rom_browser.activate().map(|picked| rom_to_load = picked),
+                        Keycode::Escape => {
+                            browser = None;
+                            Ok(())
+                        },
+                        _ => Ok(()),
+                    };
+
+                    if let Err(e) = result {
+                        println!("Failed to read directory: {e}");
+                    }
+                },
+                Event::KeyDown { keycode: Some(keycode),.. } => {
+                    let mut index: usize = 255;
+                    match keycode {
+                        Keycode::Num1 => index = 0x1,
+                        Keycode::Num2 => index = 0x2,
+                        Keycode::Num3 => index = 0x3,
+                        Keycode::Num4 => index = 0xC,
+                        Keycode::Q => index = 0x4,
+                        Keycode::W => index = 0x5,
+                        Keycode::E => index = 0x6,
+                        Keycode::R => index = 0xD,
+                        Keycode::A => index = 0x7,
+                        Keycode::S => index = 0x8,
+                        Keycode::D => index = 0x9,
+                        Keycode::F => index = 0xE,
+                        Keycode::Z => index = 0xA,
+                        Keycode::X => index = 0x0,
+                        Keycode::C => index = 0xB,
+                        Keycode::V => index = 0xF,
+                        _ => {},
+                    };
+
+                    if index < pressed_keys.len() {
+                        pressed_keys[index] = 1;
+                        last_key = index as u8;
+                    }
+                },
+                Event::KeyUp { keycode: Some(keycode), .. } => {
+                    match keycode {
+                        Keycode::Num1 => pressed_keys[0x1] = 0,
+                        Keycode::Num2 => pressed_keys[0x2] = 0,
+                        Keycode::Num3 => pressed_keys[0x3] = 0,
+                        Keycode::Num4 => pressed_keys[0xC] = 0,
+                        Keycode::Q => pressed_keys[0x4] = 0,
+                        Keycode::W => pressed_keys[0x5] = 0,
+                        Keycode::E => pressed_keys[0x6] = 0,
+                        Keycode::R => pressed_keys[0xD] = 0,
+                        Keycode::A => pressed_keys[0x7] = 0,
+                        Keycode::S => pressed_keys[0x8] = 0,
+                        Keycode::D => pressed_keys[0x9] = 0,
+                        Keycode::F => pressed_keys[0xE] = 0,
+                        Keycode::Z => pressed_keys[0xA] = 0,
+                        Keycode::X => pressed_keys[0x0] = 0,
+                        Keycode::C => pressed_keys[0xB] = 0,
+                        Keycode::V => pressed_keys[0xF] = 0,
+                        _ => {},
+                    };
+
+                    last_key = 255;
+                },
+                _ => {}
+            }
+        }
+
+        if let Some(path) = rom_to_load {
+            match switch_rom(&mut cpu, &mut screen, &mut sdl_audio_device, &path) {
+                Ok(()) => {
+                    let new_palettes = palettes_for(cpu.rom().and_then(Rom::cartridge_options).map(|options| options.palette));
+                    if new_palettes != palettes {
+                        palettes = new_palettes;
+                        theme = 0;
+                        screen.set_palette(palettes[theme]);
+                    }
+
+                    pressed_keys = [0; 16];
+                    last_key = 255;
+                    rom_directory = directory_of(&path);
+                    browser = None;
+                },
+                Err(e) => {
+                    println!("Failed to load {}: {e}", path.display());
+                    if let Some(rom_browser) = browser.as_mut() {
+                        rom_browser.show_error(e.to_string());
+                    }
+                },
+            }
+        }
+
+        match (browser.as_mut(), viewer.as_mut()) {
+            (Some(rom_browser), _) => {
+                rom_browser.render(&mut overlay);
+                screen.set_overlay(Some(&overlay));
+                screen.present();
+            },
+            (None, Some(memory_viewer)) => {
+                memory_viewer.render(&cpu, &mut overlay);
+                screen.set_overlay(Some(&overlay));
+                screen.present();
+            },
+            (None, None) => {
+                screen.set_overlay(None);
+                result = run_frame(&mut cpu, &mut screen, &mut sdl_audio_device, &pressed_keys, &mut last_key);
+                if let Err(e) = capture.add_frame(screen.framebuffer(), screen.palette()) {
+                    println!("Recording stopped: {e}");
+                }
+                if result.is_err() {
+                    break 'running;
+                }
+            },
+        }
+        wait_for_next_frame(&mut next_frame);
+    }
+
+    if let Err(e) = capture.finish(screen.framebuffer(), screen.palette()) {
+        println!("Failed to save capture: {e}");
+    }
+    if let Err(e) = sdl_audio_device.finish(cpu.frame()) {
+        println!("Failed to save audio recording: {e}");
+    }
+
+    result.map_err(FrontendError::Emulation)
+}
+
+// Value of the hex digit key pressed, if it is one
+fn hex_digit(keycode: Keycode) -> Option<u8> {
+    let name = keycode.name();
+    if name.len() != 1 {
+        return None;
+    }
+
+    u8::from_str_radix(&name, 16).ok()
+}
+
+fn toggle_fullscreen(screen: &mut SDLScreen) {
+    if let Err(e) = screen.toggle_fullscreen() {
+        println!("Failed to switch fullscreen: {e}");
+    }
+}
+
+// The directory a ROM path is in, relative paths without one are in the working directory
+fn directory_of(path: &Path) -> PathBuf {
+    match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent.to_path_buf(),
+        _ => PathBuf::from("."),
+    }
+}
+
+fn palettes_for(palette: Option<Palette>) -> Vec<Palette> {
+    palette.into_iter().chain(THEMES.iter().map(|theme| **theme)).collect()
+}
+
+// Why switch_rom kept the current ROM running
+enum SwitchError {
+    Rom(RomError),
+    Font(FontAddressError),
+}
+
+impl fmt::Display for SwitchError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SwitchError::Rom(e) => write!(f, "{e}"),
+            SwitchError::Font(e) => write!(f, "{e}"),
+        }
+    }
+}
+
+// Runs the ROM at `path` from the start in place of the current one, which keeps going if the
+// new one can't be loaded or doesn't fit next to the font
+fn switch_rom(cpu: &mut Cpu, screen: &mut SDLScreen, audio_device: &mut dyn Audible, path: &Path) -> Result<(), SwitchError> {
+    let load_address = cpu.rom().map_or(DEFAULT_LOAD_ADDRESS, Rom::load_address);
+    let rom = Rom::open(path, load_address, None).map_err(SwitchError::Rom)?;
+
+    cpu.load(&rom).map_err(SwitchError::Font)?;
+    cpu.hard_reset(screen, audio_device);
+
+    Ok(())
+}
+
+fn create_audio_device(sdl_context: &sdl2::Sdl, tone: ToneSettings) -> Box<dyn Audible> {
+    let device = SDLSoundDevice::new(sdl_context, tone);
+
+    Box::new(device) as Box<dyn Audible>
+}
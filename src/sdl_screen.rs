@@ -1,78 +1,443 @@
-use std::cell::{RefCell};
-
 use crate::drawable::Drawable;
-use crate::constants::{SCREEN_WIDTH, SCREEN_HEIGHT, PIXEL_COLOR};
+use crate::constants::{SCREEN_WIDTH, SCREEN_HEIGHT};
+use crate::palette::Palette;
+use crate::overlay::Overlay;
+use crate::scaling::{viewport, Filter, Scaling};
+
 
+use sdl2::pixels::{Color, PixelFormatEnum};
+use sdl2::rect::Rect;
+use sdl2::render::{BlendMode, Canvas, Texture, TextureCreator};
+use sdl2::video::{FullscreenType, Window, WindowContext};
 
-use sdl2::render::{Canvas, Texture};
-use sdl2::video::Window;
+// Every cell of the grid overlay is this many texels wide, the last row and column are the line
+const GRID_CELL_SIZE: usize = 8;
+const GRID_LINE_COLOR: u32 = 0xffffffff;
+// Size of the panel drawn over the screen for menus, four texels per CHIP-8 pixel
+pub const OVERLAY_WIDTH: usize = SCREEN_WIDTH * 4;
+pub const OVERLAY_HEIGHT: usize = SCREEN_HEIGHT * 4;
+// Scanlines cover the lower part of each pixel row with black at this alpha
+const SCANLINE_ALPHA: u8 = 0x70;
+// Every pixel of the CRT mask is 3x3 texels: a red, green and blue tinted column, with the
+// bottom row dimmed as the gap between rows. Multiplied onto the screen
+const CRT_MASK_CELL_SIZE: usize = 3;
+const CRT_MASK_COLUMNS: [u32; CRT_MASK_CELL_SIZE] = [0xFFFFB0B0, 0xFFB0FFB0, 0xFFB0B0FF];
+const CRT_MASK_GAP: u32 = 0xFFB0B0B0;
+// Below this brightness a decaying pixel is drawn as plain background
+const PHOSPHOR_CUTOFF: f32 = 1.0 / 256.0;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RenderMode {
+    // Show the framebuffer exactly as it is
+    Direct,
+    // Lit pixels fade out over time, persistence is the brightness kept each frame (0.0 - 1.0)
+    Phosphor { persistence: f32 },
+    // Show a pixel if it was lit in either of the last two frames
+    FrameBlend,
+}
 
-pub struct SDLScreen {
+// The textures borrow from a TextureCreator owned by the caller, so they can't outlive the renderer
+pub struct SDLScreen<'a> {
     canvas: Canvas<Window>,
-    texture: RefCell<Texture<'static>>,
-    fb: [u32; SCREEN_HEIGHT * SCREEN_WIDTH],
-    update_needed: bool,
+    texture_creator: &'a TextureCreator<WindowContext>,
+    texture: Texture<'a>,
+    grid_texture: Texture<'a>,
+    overlay_texture: Texture<'a>,
+    overlay: bool,
+    crt_mask_texture: Texture<'a>,
+    scaling: Scaling,
+    filter: Filter,
+    fb: [u8; SCREEN_HEIGHT * SCREEN_WIDTH],
+    pixels: [u32; SCREEN_HEIGHT * SCREEN_WIDTH],
+    // `pixels` as texture data
+    pixel_bytes: [u8; SCREEN_HEIGHT * SCREEN_WIDTH * 4],
+    palette: Palette,
+    grid: bool,
+    render_mode: RenderMode,
+    intensity: [f32; SCREEN_HEIGHT * SCREEN_WIDTH],
+    glow: [u8; SCREEN_HEIGHT * SCREEN_WIDTH],
+    previous_fb: [u8; SCREEN_HEIGHT * SCREEN_WIDTH],
+    dirty: bool,
 }
 
-impl Drawable for SDLScreen {
+impl Drawable for SDLScreen<'_> {
     fn draw(&mut self, x: u8, y: u8, set: u8) -> bool {
-        // Using preallocated texture we update it on each DRW opcode so we don't waste time on it
-        // on actual GPU draw
+        // Only the framebuffer is touched here, the texture is uploaded once per frame in present
+        // so sprite heavy games don't pay for a GPU upload on every pixel
 
-        let mut pixel_value: u32 = 0;
-        if set > 0 {
-            pixel_value = PIXEL_COLOR;
-        }
+        let index = (y as usize * SCREEN_WIDTH) + x as usize;
+        let collision = self.fb[index] & set;
+        self.fb[index] ^= set;
+        self.dirty = true;
 
-        let collision = self.fb[(y as usize * SCREEN_WIDTH) + x as usize] & pixel_value;
-        self.fb[(y as usize * SCREEN_WIDTH) + x as usize] ^= pixel_value;
+        collision > 0
+    }
 
-        let texture = self.texture.get_mut();
+    fn draw_sprite(&mut self, x: u8, y: u8, rows: &[u8]) -> bool {
+        let mut collision = false;
 
-        let raw_data = unsafe {
-            std::slice::from_raw_parts(self.fb.as_ptr() as *const u8, self.fb.len() * 4)
-        };
+        for (row, line) in rows.iter().enumerate() {
+            collision |= self.xor_row(x, y as usize + row, u32::from(*line) << 24, 8);
+        }
 
-        texture.update(None, raw_data, SCREEN_WIDTH * 4).unwrap();
+        collision
+    }
 
-        self.update_needed = true;
+    fn draw_sprite_16(&mut self, x: u8, y: u8, rows: &[u16]) -> bool {
+        let mut collision = false;
 
+        for (row, line) in rows.iter().enumerate() {
+            collision |= self.xor_row(x, y as usize + row, u32::from(*line) << 16, 16);
+        }
 
-        collision > 0
+        collision
     }
 
     fn cls(&mut self) {
         self.fb.iter_mut().for_each(|m| *m = 0);
-        let texture = self.texture.get_mut();
+        self.dirty = true;
+    }
 
-        let raw_data = unsafe {
-            std::slice::from_raw_parts(self.fb.as_ptr() as *const u8, self.fb.len() * 4)
+    fn present(&mut self) {
+        let redraw = match self.render_mode {
+            RenderMode::Direct => self.render_direct(),
+            RenderMode::Phosphor { persistence } => self.decay_phosphor(persistence),
+            RenderMode::FrameBlend => self.blend_frames(),
         };
 
-        texture.update(None, raw_data, SCREEN_WIDTH * 4).unwrap();
+        if redraw {
+            self.update_texture();
 
-        self.update_needed = true;
-    }
+            let (width, height) = self.canvas.output_size().unwrap();
+            let (x, y, width, height) = viewport(width, height, self.scaling);
+            let view = Rect::new(x, y, width, height);
 
-    fn present(&mut self) {
-        if self.update_needed {
-            self.canvas.copy(self.texture.get_mut(), None, None).unwrap();
+            // Clearing paints the letterbox borders
+            self.canvas.set_draw_color(Color::BLACK);
+            self.canvas.clear();
+            self.canvas.copy(&self.texture, None, view).unwrap();
+            match self.filter {
+                Filter::Scanlines => self.draw_scanlines(view),
+                Filter::CrtMask => self.canvas.copy(&self.crt_mask_texture, None, view).unwrap(),
+                Filter::None | Filter::Smooth => {},
+            }
+            if self.grid {
+                self.canvas.copy(&self.grid_texture, None, view).unwrap();
+            }
+            if self.overlay {
+                self.canvas.copy(&self.overlay_texture, None, view).unwrap();
+            }
             self.canvas.present();
-            self.update_needed = false;
         }
+
+        self.dirty = false;
     }
 }
 
-impl SDLScreen {
-    pub fn new(canvas: Canvas<Window>) -> SDLScreen {
-        let texture_creator = canvas.texture_creator();
-        let texture =  texture_creator.create_texture_target(canvas.texture_creator().default_pixel_format(), SCREEN_WIDTH as u32, SCREEN_HEIGHT as u32)
-        .unwrap();
+impl<'a> SDLScreen<'a> {
+    // `texture_creator` has to come from `canvas`
+    pub fn new(canvas: Canvas<Window>, texture_creator: &'a TextureCreator<WindowContext>, palette: Palette) -> SDLScreen<'a> {
+        let texture = Self::create_screen_texture(texture_creator, false);
+        let grid_texture = Self::create_grid_texture(texture_creator);
+        let overlay_texture = Self::create_overlay_texture(texture_creator);
+        let crt_mask_texture = Self::create_crt_mask_texture(texture_creator);
+
+        let mut screen = SDLScreen {
+            canvas,
+            texture_creator,
+            texture,
+            grid_texture,
+            overlay_texture,
+            overlay: false,
+            crt_mask_texture,
+            scaling: Scaling::default(),
+            filter: Filter::default(),
+            fb: [0; SCREEN_HEIGHT * SCREEN_WIDTH],
+            pixels: [palette.background(); SCREEN_HEIGHT * SCREEN_WIDTH],
+            pixel_bytes: [0; SCREEN_HEIGHT * SCREEN_WIDTH * 4],
+            palette,
+            grid: false,
+            render_mode: RenderMode::Direct,
+            intensity: [0.0; SCREEN_HEIGHT * SCREEN_WIDTH],
+            glow: [0; SCREEN_HEIGHT * SCREEN_WIDTH],
+            previous_fb: [0; SCREEN_HEIGHT * SCREEN_WIDTH],
+            dirty: true,
+        };
+        screen.set_palette(palette);
+
+        screen
+    }
+
+    pub fn set_palette(&mut self, palette: Palette) {
+        self.palette = palette;
+
+        let [_, r, g, b] = palette.grid.to_be_bytes();
+        self.grid_texture.set_color_mod(r, g, b);
+
+        self.dirty = true;
+    }
+
+    pub fn framebuffer(&self) -> &[u8] {
+        &self.fb
+    }
+
+    pub fn palette(&self) -> &Palette {
+        &self.palette
+    }
+
+    pub fn grid(&self) -> bool {
+        self.grid
+    }
 
-        let texture = unsafe{
-            std::mem::transmute::<_, Texture<'static>>(texture)
+    pub fn set_grid(&mut self, enabled: bool) {
+        self.grid = enabled;
+        self.dirty = true;
+    }
+
+    pub fn scaling(&self) -> Scaling {
+        self.scaling
+    }
+
+    pub fn set_scaling(&mut self, scaling: Scaling) {
+        self.scaling = scaling;
+        self.dirty = true;
+    }
+
+    pub fn filter(&self) -> Filter {
+        self.filter
+    }
+
+    pub fn set_filter(&mut self, filter: Filter) {
+        if (filter == Filter::Smooth) != (self.filter == Filter::Smooth) {
+            // The scaling quality is fixed when a texture is created
+            self.texture = Self::create_screen_texture(self.texture_creator, filter == Filter::Smooth);
+        }
+
+        self.filter = filter;
+        self.dirty = true;
+    }
+
+    pub fn toggle_fullscreen(&mut self) -> Result<(), String> {
+        let window = self.canvas.window_mut();
+        let state = match window.fullscreen_state() {
+            FullscreenType::Off => FullscreenType::Desktop,
+            _ => FullscreenType::Off,
         };
+        window.set_fullscreen(state)?;
+
+        self.dirty = true;
+        Ok(())
+    }
+
+    // Draws everything again on the next present, needed after the window was resized or uncovered
+    pub fn force_redraw(&mut self) {
+        self.dirty = true;
+    }
 
-        SDLScreen { canvas, texture: RefCell::new(texture), fb: [0; SCREEN_HEIGHT * SCREEN_WIDTH], update_needed: false }
+    // Shows the panel on top of the game until called with None, it has to be
+    // OVERLAY_WIDTH x OVERLAY_HEIGHT texels
+    pub fn set_overlay(&mut self, overlay: Option<&Overlay>) {
+        if overlay.is_none() && !self.overlay {
+            return;
+        }
+
+        if let Some(overlay) = overlay {
+            assert_eq!((overlay.width(), overlay.height()), (OVERLAY_WIDTH, OVERLAY_HEIGHT), "overlay has the wrong size");
+
+            self.overlay_texture.update(None, &texel_bytes(overlay.texels()), OVERLAY_WIDTH * 4).unwrap();
+        }
+
+        self.overlay = overlay.is_some();
+        self.dirty = true;
+    }
+
+    pub fn set_render_mode(&mut self, render_mode: RenderMode) {
+        self.render_mode = render_mode;
+
+        // Start the new mode from the current framebuffer so nothing lingers from the old one
+        for i in 0..self.fb.len() {
+            self.intensity[i] = if self.fb[i] > 0 { 1.0 } else { 0.0 };
+            self.glow[i] = self.fb[i];
+            self.previous_fb[i] = self.fb[i];
+        }
+
+        self.dirty = true;
     }
-}
\ No newline at end of file
+
+    // XORs the top `width` bits of `bits` into framebuffer row `y`, starting at column `x`
+    fn xor_row(&mut self, x: u8, y: usize, bits: u32, width: usize) -> bool {
+        let row_start = (y % SCREEN_HEIGHT) * SCREEN_WIDTH;
+        let mut collision = 0;
+
+        for col in 0..width {
+            let set = ((bits >> (31 - col)) & 1) as u8;
+            let index = row_start + (x as usize + col) % SCREEN_WIDTH;
+
+            collision |= self.fb[index] & set;
+            self.fb[index] ^= set;
+        }
+
+        self.dirty = true;
+
+        collision > 0
+    }
+
+    fn render_direct(&mut self) -> bool {
+        if !self.dirty {
+            return false;
+        }
+
+        for (pixel, planes) in self.pixels.iter_mut().zip(self.fb.iter()) {
+            *pixel = self.palette.color(*planes);
+        }
+
+        true
+    }
+
+    fn decay_phosphor(&mut self, persistence: f32) -> bool {
+        let mut fading = false;
+
+        for i in 0..self.fb.len() {
+            if self.fb[i] > 0 {
+                self.intensity[i] = 1.0;
+                self.glow[i] = self.fb[i];
+            } else if self.intensity[i] > PHOSPHOR_CUTOFF {
+                self.intensity[i] *= persistence;
+                fading = true;
+            } else {
+                self.intensity[i] = 0.0;
+            }
+
+            self.pixels[i] = blend(self.palette.background(), self.palette.color(self.glow[i]), self.intensity[i]);
+        }
+
+        // Keep presenting while pixels are still fading out even if the game didn't draw anything
+        fading || self.dirty
+    }
+
+    fn blend_frames(&mut self) -> bool {
+        if !self.dirty && self.previous_fb == self.fb {
+            return false;
+        }
+
+        for i in 0..self.fb.len() {
+            self.pixels[i] = self.palette.color(self.fb[i] | self.previous_fb[i]);
+        }
+        self.previous_fb = self.fb;
+
+        true
+    }
+
+    fn update_texture(&mut self) {
+        for (bytes, pixel) in self.pixel_bytes.chunks_exact_mut(4).zip(self.pixels.iter()) {
+            bytes.copy_from_slice(&pixel.to_ne_bytes());
+        }
+
+        self.texture.update(None, &self.pixel_bytes, SCREEN_WIDTH * 4).unwrap();
+    }
+
+    fn draw_scanlines(&mut self, view: Rect) {
+        let row_height = view.height() / SCREEN_HEIGHT as u32;
+        // Too small to draw a line inside a pixel row
+        if row_height < 2 {
+            return;
+        }
+
+        let line_height = row_height / 2;
+        let lines: Vec<Rect> = (0..SCREEN_HEIGHT as i32)
+            .map(|row| {
+                let top = view.y() + row * view.height() as i32 / SCREEN_HEIGHT as i32;
+                Rect::new(view.x(), top + (row_height - line_height) as i32, view.width(), line_height)
+            })
+            .collect();
+
+        self.canvas.set_blend_mode(BlendMode::Blend);
+        self.canvas.set_draw_color(Color::RGBA(0, 0, 0, SCANLINE_ALPHA));
+        self.canvas.fill_rects(&lines).unwrap();
+        self.canvas.set_blend_mode(BlendMode::None);
+    }
+
+    fn create_screen_texture(texture_creator: &'a TextureCreator<WindowContext>, smooth: bool) -> Texture<'a> {
+        // SDL picks up the scaling quality from this hint when the texture is created
+        sdl2::hint::set("SDL_RENDER_SCALE_QUALITY", if smooth { "linear" } else { "nearest" });
+
+        let texture = texture_creator.create_texture_target(texture_creator.default_pixel_format(), SCREEN_WIDTH as u32, SCREEN_HEIGHT as u32)
+        .unwrap();
+
+        sdl2::hint::set("SDL_RENDER_SCALE_QUALITY", "nearest");
+
+        texture
+    }
+
+    fn create_grid_texture(texture_creator: &'a TextureCreator<WindowContext>) -> Texture<'a> {
+        // Grid lines are drawn white into a transparent overlay and tinted with the palette
+        // through the texture colour mod, so switching palettes doesn't need a re-upload
+        let width = SCREEN_WIDTH * GRID_CELL_SIZE;
+        let height = SCREEN_HEIGHT * GRID_CELL_SIZE;
+
+        let mut texture = texture_creator.create_texture_static(PixelFormatEnum::ARGB8888, width as u32, height as u32)
+        .unwrap();
+
+        let mut texels: Vec<u32> = vec![0; width * height];
+        for (i, texel) in texels.iter_mut().enumerate() {
+            let (x, y) = (i % width, i / width);
+            if x % GRID_CELL_SIZE == GRID_CELL_SIZE - 1 || y % GRID_CELL_SIZE == GRID_CELL_SIZE - 1 {
+                *texel = GRID_LINE_COLOR;
+            }
+        }
+
+        texture.update(None, &texel_bytes(&texels), width * 4).unwrap();
+        texture.set_blend_mode(BlendMode::Blend);
+
+        texture
+    }
+
+    fn create_overlay_texture(texture_creator: &'a TextureCreator<WindowContext>) -> Texture<'a> {
+        let mut texture = texture_creator.create_texture_static(PixelFormatEnum::ARGB8888, OVERLAY_WIDTH as u32, OVERLAY_HEIGHT as u32)
+        .unwrap();
+        texture.set_blend_mode(BlendMode::Blend);
+
+        texture
+    }
+
+    fn create_crt_mask_texture(texture_creator: &'a TextureCreator<WindowContext>) -> Texture<'a> {
+        let width = SCREEN_WIDTH * CRT_MASK_CELL_SIZE;
+        let height = SCREEN_HEIGHT * CRT_MASK_CELL_SIZE;
+
+        let mut texture = texture_creator.create_texture_static(PixelFormatEnum::ARGB8888, width as u32, height as u32)
+        .unwrap();
+
+        let mut texels: Vec<u32> = vec![0; width * height];
+        for (i, texel) in texels.iter_mut().enumerate() {
+            let (x, y) = (i % width, i / width);
+            *texel = if y % CRT_MASK_CELL_SIZE == CRT_MASK_CELL_SIZE - 1 {
+                CRT_MASK_GAP
+            } else {
+                CRT_MASK_COLUMNS[x % CRT_MASK_CELL_SIZE]
+            };
+        }
+
+        texture.update(None, &texel_bytes(&texels), width * 4).unwrap();
+        texture.set_blend_mode(BlendMode::Mod);
+
+        texture
+    }
+}
+
+// Texture data for 32 bit texels, which SDL keeps as native endian integers
+fn texel_bytes(texels: &[u32]) -> Vec<u8> {
+    texels.iter().flat_map(|texel| texel.to_ne_bytes()).collect()
+}
+
+fn blend(from: u32, to: u32, amount: f32) -> u32 {
+    let from = from.to_be_bytes();
+    let to = to.to_be_bytes();
+    let mut result = [0u8; 4];
+
+    for channel in 0..4 {
+        let value = f32::from(from[channel]) + (f32::from(to[channel]) - f32::from(from[channel])) * amount;
+        result[channel] = value.round() as u8;
+    }
+
+    u32::from_be_bytes(result)
+}
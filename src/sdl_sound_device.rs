@@ -1,35 +1,39 @@
-use sdl2::audio::{AudioDevice, AudioSpecDesired, AudioCallback, AudioStatus};
+use std::sync::mpsc::{self, Receiver, Sender};
+
+use sdl2::audio::{AudioDevice, AudioSpecDesired, AudioCallback};
 
 use crate::audible::Audible;
+use crate::buzzer::{BuzzerEvent, BuzzerSchedule};
+use crate::tone::{Oscillator, ToneSettings};
 
-struct SquareWave {
-    phase_inc: f32,
-    phase: f32,
-    volume: f32,
+struct Buzzer {
+    oscillator: Oscillator,
+    schedule: BuzzerSchedule,
+    events: Receiver<BuzzerEvent>,
 }
 
-impl AudioCallback for SquareWave {
+impl AudioCallback for Buzzer {
     type Channel = f32;
 
     fn callback(&mut self, out: &mut [Self::Channel]) {
-        // Generate a square wave
-        for x in out.iter_mut() {
-            *x = if self.phase <= 0.5 {
-                self.volume
-            } else {
-                -self.volume
-            };
-            self.phase = (self.phase + self.phase_inc) % 1.0;
+        while let Ok(event) = self.events.try_recv() {
+            self.schedule.push(event);
         }
+
+        self.schedule.fill(&mut self.oscillator, out);
     }
 }
 
+// The device plays all the time. Buzzer events go to the audio callback through a channel
+// (lock-free, the emulator never waits on the audio thread) and open and close the oscillator's
+// gate at the sample matching their emulated frame, the envelope fades the tone in and out
 pub struct SDLSoundDevice {
-    sdl_sound_device: AudioDevice<SquareWave>,
+    sdl_sound_device: AudioDevice<Buzzer>,
+    events: Sender<BuzzerEvent>,
 }
 
 impl SDLSoundDevice {
-    pub fn new(sdl_context: &sdl2::Sdl) -> SDLSoundDevice {
+    pub fn new(sdl_context: &sdl2::Sdl, tone: ToneSettings) -> SDLSoundDevice {
         let audio_subsystem = sdl_context.audio().unwrap();
         let desired_spec = AudioSpecDesired{
             freq: Some(44_100),
@@ -37,28 +41,31 @@ impl SDLSoundDevice {
             samples: None,
         };
 
+        let (sender, receiver) = mpsc::channel();
         let device = audio_subsystem.open_playback(None, &desired_spec, |spec| {
-            SquareWave{
-                phase_inc: 440.0 / spec.freq as f32,
-                phase: 0.0,
-                volume: 0.25,
+            Buzzer {
+                oscillator: Oscillator::new(tone, spec.freq as u32),
+                schedule: BuzzerSchedule::new(spec.freq as u32),
+                events: receiver,
             }
         }).unwrap();
+        device.resume();
 
-        SDLSoundDevice { sdl_sound_device: device }
+        SDLSoundDevice { sdl_sound_device: device, events: sender }
     }
 }
 
 impl Audible for SDLSoundDevice {
-    fn enable_sound(&mut self) {
-        if self.sdl_sound_device.status() == AudioStatus::Paused {
-            self.sdl_sound_device.resume();
-        }
+    fn enable_sound(&mut self, frame: u64) {
+        // Only fails once the audio callback is gone, and then there is nothing to play anyway
+        let _ = self.events.send(BuzzerEvent { frame, on: true });
     }
 
-    fn disable_sound(&mut self) {
-        if self.sdl_sound_device.status() == AudioStatus::Playing {
-            self.sdl_sound_device.pause();
-        }
+    fn disable_sound(&mut self, frame: u64) {
+        let _ = self.events.send(BuzzerEvent { frame, on: false });
+    }
+
+    fn set_tone(&mut self, tone: ToneSettings) {
+        self.sdl_sound_device.lock().oscillator.set_settings(tone);
     }
 }
@@ -0,0 +1,213 @@
+use std::f32::consts::TAU;
+
+pub const MIN_FREQUENCY: f32 = 20.0;
+pub const MAX_FREQUENCY: f32 = 4000.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Waveform {
+    #[default]
+    Square,
+    Sine,
+    Triangle,
+    Noise,
+}
+
+impl Waveform {
+    pub const ALL: [Waveform; 4] = [Waveform::Square, Waveform::Sine, Waveform::Triangle, Waveform::Noise];
+
+    pub fn from_name(name: &str) -> Option<Waveform> {
+        Waveform::ALL.into_iter().find(|waveform| waveform.name() == name)
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Waveform::Square => "square",
+            Waveform::Sine => "sine",
+            Waveform::Triangle => "triangle",
+            Waveform::Noise => "noise",
+        }
+    }
+
+    pub fn next(&self) -> Waveform {
+        let index = Waveform::ALL.iter().position(|waveform| waveform == self).unwrap();
+
+        Waveform::ALL[(index + 1) % Waveform::ALL.len()]
+    }
+}
+
+// What the buzzer sounds like. Attack and release are in seconds, volume goes from 0.0 to 1.0
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ToneSettings {
+    pub frequency: f32,
+    pub waveform: Waveform,
+    pub volume: f32,
+    pub attack: f32,
+    pub release: f32,
+}
+
+impl Default for ToneSettings {
+    fn default() -> ToneSettings {
+        ToneSettings {
+            frequency: 440.0,
+            waveform: Waveform::Square,
+            volume: 0.25,
+            attack: 0.005,
+            release: 0.01,
+        }
+    }
+}
+
+// Generates the buzzer tone one sample at a time. The gate opens and closes the tone, the
+// envelope ramps the level up and down over attack/release so switching it doesn't click
+pub struct Oscillator {
+    settings: ToneSettings,
+    sample_rate: f32,
+    phase: f32,
+    gate: bool,
+    level: f32,
+    noise_state: u32,
+    noise_value: f32,
+}
+
+impl Oscillator {
+    pub fn new(settings: ToneSettings, sample_rate: u32) -> Oscillator {
+        Oscillator {
+            settings,
+            sample_rate: sample_rate as f32,
+            phase: 0.0,
+            gate: false,
+            level: 0.0,
+            noise_state: 0x1234_5678,
+            noise_value: 0.0,
+        }
+    }
+
+    pub fn set_settings(&mut self, settings: ToneSettings) {
+        self.settings = settings;
+    }
+
+    pub fn set_gate(&mut self, open: bool) {
+        self.gate = open;
+    }
+
+    pub fn fill(&mut self, out: &mut [f32]) {
+        for sample in out.iter_mut() {
+            *sample = self.next_sample();
+        }
+    }
+
+    pub fn next_sample(&mut self) -> f32 {
+        self.advance_envelope();
+        if self.level == 0.0 {
+            return 0.0;
+        }
+
+        let value = match self.settings.waveform {
+            Waveform::Square => if self.phase < 0.5 { 1.0 } else { -1.0 },
+            Waveform::Sine => (self.phase * TAU).sin(),
+            Waveform::Triangle => 1.0 - 4.0 * (self.phase - 0.5).abs(),
+            Waveform::Noise => self.noise_value,
+        };
+
+        self.phase += self.settings.frequency / self.sample_rate;
+        if self.phase >= 1.0 {
+            self.phase %= 1.0;
+            // Noise picks a new random level once per period, so the frequency sets its pitch
+            self.noise_value = self.next_noise();
+        }
+
+        value * self.settings.volume * self.level
+    }
+
+    fn advance_envelope(&mut self) {
+        let (target, duration) = if self.gate {
+            (1.0, self.settings.attack)
+        } else {
+            (0.0, self.settings.release)
+        };
+
+        let step = 1.0 / (duration * self.sample_rate).max(1.0);
+        self.level = if self.level < target {
+            (self.level + step).min(target)
+        } else {
+            (self.level - step).max(target)
+        };
+    }
+
+    // xorshift32, plenty random for a buzzer
+    fn next_noise(&mut self) -> f32 {
+        self.noise_state ^= self.noise_state << 13;
+        self.noise_state ^= self.noise_state >> 17;
+        self.noise_state ^= self.noise_state << 5;
+
+        (self.noise_state as f32 / u32::MAX as f32) * 2.0 - 1.0
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE_RATE: u32 = 44_100;
+
+    fn samples(oscillator: &mut Oscillator, count: usize) -> Vec<f32> {
+        let mut out = vec![0.0; count];
+        oscillator.fill(&mut out);
+        out
+    }
+
+    #[test]
+    fn silent_until_the_gate_opens() {
+        let mut oscillator = Oscillator::new(ToneSettings::default(), SAMPLE_RATE);
+        assert!(samples(&mut oscillator, 100).iter().all(|s| *s == 0.0));
+    }
+
+    #[test]
+    fn attack_and_release_ramp_the_level() {
+        let settings = ToneSettings { waveform: Waveform::Square, volume: 1.0, attack: 0.01, release: 0.01, ..Default::default() };
+        let mut oscillator = Oscillator::new(settings, SAMPLE_RATE);
+
+        oscillator.set_gate(true);
+        let attack = samples(&mut oscillator, 441);
+        assert!(attack[0].abs() < 0.01);
+        assert!((attack[440].abs() - 1.0).abs() < 0.001);
+
+        oscillator.set_gate(false);
+        let release = samples(&mut oscillator, 442);
+        assert!(release[0].abs() > 0.99);
+        assert_eq!(release[441], 0.0);
+    }
+
+    #[test]
+    fn waveforms_stay_within_volume() {
+        for waveform in Waveform::ALL {
+            let settings = ToneSettings { waveform, volume: 0.5, attack: 0.0, ..Default::default() };
+            let mut oscillator = Oscillator::new(settings, SAMPLE_RATE);
+            oscillator.set_gate(true);
+
+            let out = samples(&mut oscillator, 1000);
+            assert!(out.iter().all(|s| s.abs() <= 0.5), "{waveform:?}");
+            assert!(out.iter().any(|s| s.abs() > 0.1), "{waveform:?}");
+        }
+    }
+
+    #[test]
+    fn square_wave_period_follows_frequency() {
+        let settings = ToneSettings { frequency: 441.0, attack: 0.0, ..Default::default() };
+        let mut oscillator = Oscillator::new(settings, SAMPLE_RATE);
+        oscillator.set_gate(true);
+
+        // 100 samples per period, half high and half low (give or take rounding at the edge)
+        let out = samples(&mut oscillator, 100);
+        assert!(out[..49].iter().all(|s| *s > 0.0));
+        assert!(out[51..].iter().all(|s| *s < 0.0));
+    }
+
+    #[test]
+    fn waveform_names() {
+        assert_eq!(Waveform::from_name("triangle"), Some(Waveform::Triangle));
+        assert_eq!(Waveform::from_name("saw"), None);
+        assert_eq!(Waveform::Noise.next(), Waveform::Square);
+    }
+}
@@ -0,0 +1,255 @@
+use std::collections::VecDeque;
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::ops::RangeInclusive;
+
+use crate::cpu::CpuError;
+use crate::instructions::Instructions;
+use crate::options::Options;
+
+// Wide enough for the longest mnemonic, "DRW VX, VY, nibble"
+const MNEMONIC_WIDTH: usize = 18;
+
+// One executed instruction and the machine state just before it ran
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TraceEntry {
+    // Instructions executed since power on
+    pub cycle: u64,
+    pub pc: u16,
+    pub opcode: u16,
+    pub instruction: Instructions,
+    pub mnemonic: &'static str,
+    pub registers: [u8; 16],
+    pub i: u16,
+    pub sp: u8,
+}
+
+impl fmt::Display for TraceEntry {
+    // Fixed width columns, so traces of two runs (or two emulators) can be diffed line by line
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:>10} {:04X} {:04X} {:<width$}", self.cycle, self.pc, self.opcode, self.mnemonic, width = MNEMONIC_WIDTH)?;
+        for register in self.registers {
+            write!(f, " {register:02X}")?;
+        }
+        write!(f, " I:{:04X} SP:{:02X}", self.i, self.sp)
+    }
+}
+
+// Which instructions get traced, everything by default
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TraceFilter {
+    pub addresses: Option<RangeInclusive<u16>>,
+    // Empty for all of them
+    pub instructions: Vec<Instructions>,
+}
+
+impl TraceFilter {
+    pub fn matches(&self, entry: &TraceEntry) -> bool {
+        let in_range = self.addresses.as_ref().map_or(true, |addresses| addresses.contains(&entry.pc));
+
+        in_range && (self.instructions.is_empty() || self.instructions.contains(&entry.instruction))
+    }
+}
+
+// Logs executed instructions. It either writes every one right away, or keeps only the last few
+// and writes them out when the CPU fails, which costs nothing until something goes wrong
+pub struct Tracer {
+    output: Box<dyn Write>,
+    filter: TraceFilter,
+    // Only used with a ring buffer
+    recent: VecDeque<TraceEntry>,
+    ring_size: Option<usize>,
+    // Set after a write failed, the rest of the trace is dropped
+    failed: bool,
+}
+
+impl Tracer {
+    pub fn new(output: Box<dyn Write>, filter: TraceFilter) -> Tracer {
+        Tracer {
+            output,
+            filter,
+            recent: VecDeque::new(),
+            ring_size: None,
+            failed: false,
+        }
+    }
+
+    // Keeps the last `size` instructions, written when the CPU reports an error
+    pub fn ring(output: Box<dyn Write>, filter: TraceFilter, size: usize) -> Tracer {
+        Tracer {
+            recent: VecDeque::with_capacity(size),
+            ring_size: Some(size),
+            ..Tracer::new(output, filter)
+        }
+    }
+
+    pub fn record(&mut self, entry: TraceEntry) {
+        if !self.filter.matches(&entry) {
+            return;
+        }
+
+        match self.ring_size {
+            Some(size) => {
+                if self.recent.len() == size {
+                    self.recent.pop_front();
+                }
+                if size > 0 {
+                    self.recent.push_back(entry);
+                }
+            },
+            None => self.write(|output| writeln!(output, "{entry}")),
+        }
+    }
+
+    // Called by the CPU when it stops with an error
+    pub fn report(&mut self, error: &CpuError) {
+        let recent = std::mem::take(&mut self.recent);
+        self.write(|output| {
+            if !recent.is_empty() {
+                writeln!(output, "Last {} instructions:", recent.len())?;
+            }
+            for entry in &recent {
+                writeln!(output, "{entry}")?;
+            }
+            writeln!(output, "CPU error: {error}")?;
+            output.flush()
+        });
+    }
+
+    fn write<F: FnOnce(&mut dyn Write) -> io::Result<()>>(&mut self, write: F) {
+        if self.failed {
+            return;
+        }
+
+        if let Err(e) = write(self.output.as_mut()) {
+            eprintln!("Failed to write trace: {e}");
+            self.failed = true;
+        }
+    }
+}
+
+// The tracer asked for on the command line, writing to --trace-file or stderr
+pub fn tracer_for(options: &Options) -> io::Result<Option<Tracer>> {
+    if !options.trace {
+        return Ok(None);
+    }
+
+    let output: Box<dyn Write> = match &options.trace_file {
+        Some(path) => Box::new(BufWriter::new(File::create(path)?)),
+        None => Box::new(BufWriter::new(io::stderr())),
+    };
+    let filter = options.trace_filter.clone();
+
+    Ok(Some(match options.trace_ring {
+        Some(size) => Tracer::ring(output, filter, size),
+        None => Tracer::new(output, filter),
+    }))
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cpu::Cpu;
+    use crate::headless_screen::HeadlessScreen;
+    use crate::null_sound_device::NullSoundDevice;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    // Lets the test read what the tracer wrote into its boxed output
+    #[derive(Clone, Default)]
+    struct SharedBuffer(Rc<RefCell<Vec<u8>>>);
+
+    impl Write for SharedBuffer {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.borrow_mut().write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl SharedBuffer {
+        fn lines(&self) -> Vec<String> {
+            String::from_utf8(self.0.borrow().clone()).unwrap().lines().map(str::to_string).collect()
+        }
+    }
+
+    fn entry(cycle: u64, pc: u16, instruction: Instructions, mnemonic: &'static str) -> TraceEntry {
+        TraceEntry { cycle, pc, opcode: 0x00E0, instruction, mnemonic, registers: [0; 16], i: 0x123, sp: 0xFF }
+    }
+
+    #[test]
+    fn entries_have_fixed_columns() {
+        let mut registers = [0; 16];
+        registers[0xF] = 0xAB;
+        let entry = TraceEntry { registers, ..entry(42, 0x200, Instructions::Cls, "CLS") };
+
+        assert_eq!(
+            entry.to_string(),
+            "        42 0200 00E0 CLS                00 00 00 00 00 00 00 00 00 00 00 00 00 00 00 AB I:0123 SP:FF"
+        );
+    }
+
+    #[test]
+    fn filter_by_address_and_instruction() {
+        let filter = TraceFilter { addresses: Some(0x200..=0x2FF), instructions: vec![Instructions::DrwVxVyNib] };
+
+        assert!(filter.matches(&entry(0, 0x210, Instructions::DrwVxVyNib, "DRW VX, VY, nibble")));
+        assert!(!filter.matches(&entry(0, 0x300, Instructions::DrwVxVyNib, "DRW VX, VY, nibble")));
+        assert!(!filter.matches(&entry(0, 0x210, Instructions::Cls, "CLS")));
+        assert!(TraceFilter::default().matches(&entry(0, 0x300, Instructions::Cls, "CLS")));
+    }
+
+    #[test]
+    fn filter_tells_load_instructions_apart() {
+        let filter = TraceFilter { addresses: None, instructions: vec![Instructions::from_name("ldiaddr").unwrap()] };
+
+        assert!(filter.matches(&entry(0, 0x200, Instructions::LdIAddr, "LD I, addr")));
+        assert!(!filter.matches(&entry(0, 0x200, Instructions::LdVxByte, "LD Vx, byte")));
+        assert!(!filter.matches(&entry(0, 0x200, Instructions::LdIVx, "LD [I], Vx")));
+        assert_eq!(Instructions::from_name("LD"), None);
+    }
+
+    #[test]
+    fn ring_keeps_the_last_instructions_until_an_error() {
+        let buffer = SharedBuffer::default();
+        let mut tracer = Tracer::ring(Box::new(buffer.clone()), TraceFilter::default(), 2);
+
+        for cycle in 0..5 {
+            tracer.record(entry(cycle, 0x200, Instructions::Cls, "CLS"));
+        }
+        assert!(buffer.lines().is_empty());
+
+        tracer.report(&CpuError::StackUnderflow { pc: 0x200 });
+        let lines = buffer.lines();
+        assert_eq!(lines.len(), 4);
+        assert_eq!(lines[0], "Last 2 instructions:");
+        assert!(lines[1].trim_start().starts_with("3 "));
+        assert!(lines[3].starts_with("CPU error: "));
+    }
+
+    #[test]
+    fn cpu_traces_until_it_fails() {
+        let buffer = SharedBuffer::default();
+        let mut cpu = Cpu::new();
+        // LD V1, 0x42, then RET with nothing on the stack
+        cpu.ram[0x200..0x204].copy_from_slice(&[0x61, 0x42, 0x00, 0xEE]);
+        cpu.set_tracer(Some(Tracer::new(Box::new(buffer.clone()), TraceFilter::default())));
+
+        let mut screen = HeadlessScreen::new();
+        let mut audio = NullSoundDevice::new();
+        let mut last_key = 255;
+        assert!(cpu.step(&mut screen, &mut audio, &[0; 16], &mut last_key).is_ok());
+        assert!(cpu.step(&mut screen, &mut audio, &[0; 16], &mut last_key).is_err());
+
+        let lines = buffer.lines();
+        assert_eq!(lines.len(), 3);
+        assert!(lines[0].starts_with("         0 0200 6142 LD Vx, byte"));
+        assert!(lines[1].starts_with("         1 0202 00EE RET"));
+        assert!(lines[1].contains(" 00 42 00 "));
+        assert_eq!(lines[2], "CPU error: Return with an empty stack at 0x202");
+    }
+}
@@ -0,0 +1,61 @@
+use std::time::Instant;
+
+use crate::capture::Capture;
+use crate::cpu::Cpu;
+use crate::frame::{run_frame, wait_for_next_frame};
+use crate::frontend_error::FrontendError;
+use crate::options::Options;
+use crate::palette::{Palette, THEMES};
+use crate::tui_keypad::{TUIKeypad, TUICommand};
+use crate::tui_screen::TUIScreen;
+use crate::tui_sound_device::TUISoundDevice;
+use crate::wav_recorder::RecordedDevice;
+
+pub fn run(mut cpu: Cpu, palette: Option<Palette>, options: &Options) -> Result<(), FrontendError> {
+    let palettes: Vec<Palette> = palette.into_iter().chain(THEMES.iter().map(|theme| **theme)).collect();
+    let mut theme = 0;
+    let mut audio_device = RecordedDevice::new(TUISoundDevice::new(), options)?;
+    let mut capture = Capture::new(options, &palettes[theme])?;
+    let mut screen = TUIScreen::new(palettes[theme]).map_err(FrontendError::Terminal)?;
+    let mut keypad = TUIKeypad::new();
+
+    let mut pressed_keys = [0; 16];
+    let mut last_key: u8 = 255;
+
+    let mut next_frame = Instant::now();
+
+    let mut result = Ok(());
+    'running: loop {
+        let commands = keypad.poll(&mut pressed_keys, &mut last_key).unwrap_or_else(|_| vec![TUICommand::Quit]);
+        for command in commands {
+            match command {
+                TUICommand::Quit => break 'running,
+                TUICommand::CyclePalette => {
+                    theme = (theme + 1) % palettes.len();
+                    screen.set_palette(palettes[theme]);
+                },
+                TUICommand::SoftReset => cpu.soft_reset(&mut screen, &mut audio_device),
+                TUICommand::HardReset => cpu.hard_reset(&mut screen, &mut audio_device),
+            }
+        }
+
+        result = run_frame(&mut cpu, &mut screen, &mut audio_device, &pressed_keys, &mut last_key);
+        // Printing would garble the terminal, a failed recording just stops
+        let _ = capture.add_frame(screen.framebuffer(), screen.palette());
+        if result.is_err() {
+            break 'running;
+        }
+        wait_for_next_frame(&mut next_frame);
+    }
+
+    let capture_result = capture.finish(screen.framebuffer(), screen.palette());
+    drop(screen);
+    if let Err(e) = capture_result {
+        println!("Failed to save capture: {e}");
+    }
+    if let Err(e) = audio_device.finish(cpu.frame()) {
+        println!("Failed to save audio recording: {e}");
+    }
+
+    result.map_err(FrontendError::Emulation)
+}
@@ -0,0 +1,97 @@
+use std::io;
+use std::time::Duration;
+
+use crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+
+// Most terminals only report key presses, never releases. A pressed key is treated as held
+// for this many frames, terminal auto repeat keeps refreshing it while the key stays down
+const HOLD_FRAMES: u8 = 10;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TUICommand {
+    Quit,
+    CyclePalette,
+    SoftReset,
+    HardReset,
+}
+
+#[derive(Default)]
+pub struct TUIKeypad {
+    held_frames: [u8; 16],
+}
+
+impl TUIKeypad {
+    pub fn new() -> TUIKeypad {
+        TUIKeypad { held_frames: [0; 16] }
+    }
+
+    // Reads all pending terminal events without blocking, called once per frame
+    pub fn poll(&mut self, pressed_keys: &mut [u8; 16], last_key: &mut u8) -> io::Result<Vec<TUICommand>> {
+        let mut commands = vec![];
+
+        for (index, frames) in self.held_frames.iter_mut().enumerate() {
+            if *frames > 0 {
+                *frames -= 1;
+
+                if *frames == 0 {
+                    pressed_keys[index] = 0;
+                    *last_key = 255;
+                }
+            }
+        }
+
+        while event::poll(Duration::ZERO)? {
+            let Event::Key(KeyEvent { code, modifiers, kind, .. }) = event::read()? else {
+                continue;
+            };
+
+            match code {
+                KeyCode::Esc => commands.push(TUICommand::Quit),
+                KeyCode::Char('c') if modifiers.contains(KeyModifiers::CONTROL) => commands.push(TUICommand::Quit),
+                KeyCode::F(1) if kind == KeyEventKind::Press => commands.push(TUICommand::CyclePalette),
+                KeyCode::F(5) if kind == KeyEventKind::Press => {
+                    let shift = modifiers.contains(KeyModifiers::SHIFT);
+                    commands.push(if shift { TUICommand::HardReset } else { TUICommand::SoftReset });
+                },
+                KeyCode::Char(c) => {
+                    if let Some(index) = keypad_index(c) {
+                        if kind == KeyEventKind::Release {
+                            self.held_frames[index] = 0;
+                            pressed_keys[index] = 0;
+                            *last_key = 255;
+                        } else {
+                            self.held_frames[index] = HOLD_FRAMES;
+                            pressed_keys[index] = 1;
+                            *last_key = index as u8;
+                        }
+                    }
+                },
+                _ => {},
+            }
+        }
+
+        Ok(commands)
+    }
+}
+
+fn keypad_index(c: char) -> Option<usize> {
+    match c.to_ascii_lowercase() {
+        '1' => Some(0x1),
+        '2' => Some(0x2),
+        '3' => Some(0x3),
+        '4' => Some(0xC),
+        'q' => Some(0x4),
+        'w' => Some(0x5),
+        'e' => Some(0x6),
+        'r' => Some(0xD),
+        'a' => Some(0x7),
+        's' => Some(0x8),
+        'd' => Some(0x9),
+        'f' => Some(0xE),
+        'z' => Some(0xA),
+        'x' => Some(0x0),
+        'c' => Some(0xB),
+        'v' => Some(0xF),
+        _ => None,
+    }
+}
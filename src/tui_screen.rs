@@ -0,0 +1,128 @@
+use std::io::{self, Stdout, Write};
+
+use crossterm::{cursor, execute, queue, terminal};
+use crossterm::style::{Color, Print, ResetColor, SetColors, Colors};
+
+use crate::drawable::Drawable;
+use crate::constants::{SCREEN_WIDTH, SCREEN_HEIGHT};
+use crate::palette::Palette;
+
+// Every terminal cell shows two pixels stacked on top of each other: the upper one as
+// the foreground of the half block, the lower one as the background
+const UPPER_HALF_BLOCK: char = '\u{2580}';
+
+pub struct TUIScreen {
+    stdout: Stdout,
+    fb: [u8; SCREEN_HEIGHT * SCREEN_WIDTH],
+    // What is currently on the terminal, used to only redraw rows that changed
+    presented: [u8; SCREEN_HEIGHT * SCREEN_WIDTH],
+    palette: Palette,
+    dirty: bool,
+    full_redraw: bool,
+}
+
+impl Drawable for TUIScreen {
+    fn draw(&mut self, x: u8, y: u8, set: u8) -> bool {
+        let index = (y as usize * SCREEN_WIDTH) + x as usize;
+        let collision = self.fb[index] & set;
+        self.fb[index] ^= set;
+        self.dirty = true;
+
+        collision > 0
+    }
+
+    fn cls(&mut self) {
+        self.fb.iter_mut().for_each(|m| *m = 0);
+        self.dirty = true;
+    }
+
+    fn present(&mut self) {
+        if !self.dirty && !self.full_redraw {
+            return;
+        }
+
+        // The terminal going away mid frame isn't worth crashing the emulator over
+        let _ = self.write_frame();
+
+        self.presented = self.fb;
+        self.dirty = false;
+        self.full_redraw = false;
+    }
+}
+
+impl TUIScreen {
+    // Switches the terminal to raw mode on the alternate screen, restored again on drop
+    pub fn new(palette: Palette) -> io::Result<TUIScreen> {
+        let mut stdout = io::stdout();
+
+        terminal::enable_raw_mode()?;
+        execute!(stdout, terminal::EnterAlternateScreen, cursor::Hide, terminal::Clear(terminal::ClearType::All))?;
+
+        Ok(TUIScreen {
+            stdout,
+            fb: [0; SCREEN_HEIGHT * SCREEN_WIDTH],
+            presented: [0; SCREEN_HEIGHT * SCREEN_WIDTH],
+            palette,
+            dirty: false,
+            full_redraw: true,
+        })
+    }
+
+    pub fn set_palette(&mut self, palette: Palette) {
+        self.palette = palette;
+        self.full_redraw = true;
+    }
+
+    pub fn framebuffer(&self) -> &[u8] {
+        &self.fb
+    }
+
+    pub fn palette(&self) -> &Palette {
+        &self.palette
+    }
+
+    fn write_frame(&mut self) -> io::Result<()> {
+        for cell_row in 0..SCREEN_HEIGHT / 2 {
+            let top = cell_row * 2 * SCREEN_WIDTH;
+            let bottom = top + SCREEN_WIDTH;
+            let rows = top..bottom + SCREEN_WIDTH;
+
+            if !self.full_redraw && self.fb[rows.clone()] == self.presented[rows] {
+                continue;
+            }
+
+            queue!(self.stdout, cursor::MoveTo(0, cell_row as u16))?;
+
+            let mut current_colors = None;
+            for x in 0..SCREEN_WIDTH {
+                let colors = Colors::new(
+                    to_terminal_color(self.palette.color(self.fb[top + x])),
+                    to_terminal_color(self.palette.color(self.fb[bottom + x])),
+                );
+
+                if current_colors != Some(colors) {
+                    queue!(self.stdout, SetColors(colors))?;
+                    current_colors = Some(colors);
+                }
+
+                queue!(self.stdout, Print(UPPER_HALF_BLOCK))?;
+            }
+        }
+
+        queue!(self.stdout, ResetColor)?;
+        self.stdout.flush()
+    }
+}
+
+impl Drop for TUIScreen {
+    fn drop(&mut self) {
+        let _ = execute!(self.stdout, ResetColor, cursor::Show, terminal::LeaveAlternateScreen);
+        let _ = terminal::disable_raw_mode();
+    }
+}
+
+fn to_terminal_color(color: u32) -> Color {
+    let [_, r, g, b] = color.to_be_bytes();
+
+    Color::Rgb { r, g, b }
+}
@@ -0,0 +1,29 @@
+use std::io::{self, Write};
+
+use crate::audible::Audible;
+
+// Terminals can't play a tone for a given time, so each beep just rings the bell once
+#[derive(Default)]
+pub struct TUISoundDevice {
+    ringing: bool,
+}
+
+impl TUISoundDevice {
+    pub fn new() -> TUISoundDevice {
+        TUISoundDevice { ringing: false }
+    }
+}
+
+impl Audible for TUISoundDevice {
+    fn enable_sound(&mut self, _frame: u64) {
+        if !self.ringing {
+            let mut stdout = io::stdout();
+            let _ = stdout.write_all(b"\x07").and_then(|_| stdout.flush());
+            self.ringing = true;
+        }
+    }
+
+    fn disable_sound(&mut self, _frame: u64) {
+        self.ringing = false;
+    }
+}
@@ -0,0 +1,262 @@
+use std::fs::File;
+use std::io::{BufWriter, Seek, Write};
+
+use hound::{SampleFormat, WavSpec, WavWriter};
+
+use crate::audible::Audible;
+use crate::constants::FRAMES_PER_SECOND;
+use crate::options::Options;
+use crate::tone::{Oscillator, ToneSettings};
+
+pub const DEFAULT_SAMPLE_RATE: u32 = 44_100;
+pub const MIN_SAMPLE_RATE: u32 = 8_000;
+pub const MAX_SAMPLE_RATE: u32 = 192_000;
+
+// Renders the buzzer into a 16 bit mono WAV file. Samples are generated from the emulated frame
+// of every event rather than the wall clock, so a recording is the same on every run and works
+// without any audio hardware. The file starts at emulated frame 0
+pub struct WavRecorder<W: Write + Seek> {
+    // Taken out when the recording is finished
+    writer: Option<WavWriter<W>>,
+    oscillator: Oscillator,
+    sample_rate: u64,
+    // Samples written so far
+    position: u64,
+    last_frame: u64,
+    // First write error, the Audible calls have no way to report it
+    error: Option<hound::Error>,
+}
+
+impl WavRecorder<BufWriter<File>> {
+    pub fn create(path: &str, tone: ToneSettings, sample_rate: u32) -> Result<Self, hound::Error> {
+        let writer = WavWriter::create(path, spec(sample_rate))?;
+
+        Ok(WavRecorder::with_writer(writer, tone, sample_rate))
+    }
+}
+
+impl<W: Write + Seek> WavRecorder<W> {
+    pub fn new(writer: W, tone: ToneSettings, sample_rate: u32) -> Result<Self, hound::Error> {
+        let writer = WavWriter::new(writer, spec(sample_rate))?;
+
+        Ok(WavRecorder::with_writer(writer, tone, sample_rate))
+    }
+
+    fn with_writer(writer: WavWriter<W>, tone: ToneSettings, sample_rate: u32) -> Self {
+        WavRecorder {
+            writer: Some(writer),
+            oscillator: Oscillator::new(tone, sample_rate),
+            sample_rate: u64::from(sample_rate),
+            position: 0,
+            last_frame: 0,
+            error: None,
+        }
+    }
+
+    // Renders up to the start of emulated frame `frame` and completes the file. Dropping the
+    // recorder does the same, but stops at the last buzzer event and can't report errors
+    pub fn finish(mut self, frame: u64) -> Result<(), hound::Error> {
+        self.render_until(frame);
+        self.complete()
+    }
+
+    fn complete(&mut self) -> Result<(), hound::Error> {
+        if let Some(error) = self.error.take() {
+            return Err(error);
+        }
+
+        match self.writer.take() {
+            Some(writer) => writer.finalize(),
+            None => Ok(()),
+        }
+    }
+
+    fn render_until(&mut self, frame: u64) {
+        self.last_frame = self.last_frame.max(frame);
+        let Some(writer) = self.writer.as_mut() else {
+            return;
+        };
+
+        let end = frame * self.sample_rate / u64::from(FRAMES_PER_SECOND);
+        while self.position < end {
+            let sample = (self.oscillator.next_sample() * f32::from(i16::MAX)) as i16;
+            if let Err(e) = writer.write_sample(sample) {
+                self.error = Some(e);
+                self.writer = None;
+                return;
+            }
+            self.position += 1;
+        }
+    }
+}
+
+impl<W: Write + Seek> Audible for WavRecorder<W> {
+    fn enable_sound(&mut self, frame: u64) {
+        self.render_until(frame);
+        self.oscillator.set_gate(true);
+    }
+
+    fn disable_sound(&mut self, frame: u64) {
+        self.render_until(frame);
+        self.oscillator.set_gate(false);
+    }
+
+    fn set_tone(&mut self, tone: ToneSettings) {
+        self.oscillator.set_settings(tone);
+    }
+}
+
+impl<W: Write + Seek> Drop for WavRecorder<W> {
+    fn drop(&mut self) {
+        if self.writer.is_none() && self.error.is_none() {
+            return;
+        }
+
+        self.render_until(self.last_frame);
+        if let Err(e) = self.complete() {
+            eprintln!("Failed to write audio recording: {e}");
+        }
+    }
+}
+
+// A frontend's audio device, plus a recording of everything it plays when --record-audio was given
+pub struct RecordedDevice<A: Audible> {
+    device: A,
+    recorder: Option<WavRecorder<BufWriter<File>>>,
+}
+
+impl<A: Audible> RecordedDevice<A> {
+    pub fn new(device: A, options: &Options) -> Result<Self, hound::Error> {
+        let recorder = match &options.record_audio {
+            Some(path) => Some(WavRecorder::create(path, options.tone, options.record_sample_rate)?),
+            None => None,
+        };
+
+        Ok(RecordedDevice { device, recorder })
+    }
+
+    // Completes the recording at emulated frame `frame`, the end of the run, so the silence
+    // after the last beep is kept
+    pub fn finish(&mut self, frame: u64) -> Result<(), hound::Error> {
+        match self.recorder.take() {
+            Some(recorder) => recorder.finish(frame),
+            None => Ok(()),
+        }
+    }
+}
+
+impl<A: Audible> Audible for RecordedDevice<A> {
+    fn enable_sound(&mut self, frame: u64) {
+        self.device.enable_sound(frame);
+        if let Some(recorder) = self.recorder.as_mut() {
+            recorder.enable_sound(frame);
+        }
+    }
+
+    fn disable_sound(&mut self, frame: u64) {
+        self.device.disable_sound(frame);
+        if let Some(recorder) = self.recorder.as_mut() {
+            recorder.disable_sound(frame);
+        }
+    }
+
+    fn set_tone(&mut self, tone: ToneSettings) {
+        self.device.set_tone(tone);
+        if let Some(recorder) = self.recorder.as_mut() {
+            recorder.set_tone(tone);
+        }
+    }
+}
+
+fn spec(sample_rate: u32) -> WavSpec {
+    WavSpec {
+        channels: 1,
+        sample_rate,
+        bits_per_sample: 16,
+        sample_format: SampleFormat::Int,
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::null_sound_device::NullSoundDevice;
+    use std::io::Cursor;
+
+    const SAMPLE_RATE: u32 = 48_000;
+    const SAMPLES_PER_FRAME: usize = 800;
+
+    fn tone() -> ToneSettings {
+        ToneSettings { attack: 0.0, release: 0.0, ..Default::default() }
+    }
+
+    fn samples(wav: Vec<u8>) -> (WavSpec, Vec<i16>) {
+        let reader = hound::WavReader::new(Cursor::new(wav)).unwrap();
+        let spec = reader.spec();
+        (spec, reader.into_samples().map(Result::unwrap).collect())
+    }
+
+    #[test]
+    fn beeps_land_on_their_emulated_frames() {
+        let mut wav = Cursor::new(vec![]);
+        let mut recorder = WavRecorder::new(&mut wav, tone(), SAMPLE_RATE).unwrap();
+        recorder.enable_sound(2);
+        recorder.disable_sound(5);
+        recorder.finish(10).unwrap();
+
+        let (spec, samples) = samples(wav.into_inner());
+        assert_eq!(spec.sample_rate, SAMPLE_RATE);
+        assert_eq!(samples.len(), SAMPLES_PER_FRAME * 10);
+
+        let first = samples.iter().position(|s| *s != 0).unwrap();
+        let last = samples.iter().rposition(|s| *s != 0).unwrap();
+        assert_eq!(first, SAMPLES_PER_FRAME * 2);
+        assert_eq!(last + 1, SAMPLES_PER_FRAME * 5);
+    }
+
+    #[test]
+    fn dropping_completes_the_file_at_the_last_event() {
+        let mut wav = Cursor::new(vec![]);
+        {
+            let mut recorder = WavRecorder::new(&mut wav, tone(), SAMPLE_RATE).unwrap();
+            recorder.enable_sound(1);
+            recorder.disable_sound(3);
+        }
+
+        let (_, samples) = samples(wav.into_inner());
+        assert_eq!(samples.len(), SAMPLES_PER_FRAME * 3);
+    }
+
+    #[test]
+    fn tone_changes_are_recorded() {
+        let mut wav = Cursor::new(vec![]);
+        let mut recorder = WavRecorder::new(&mut wav, tone(), SAMPLE_RATE).unwrap();
+        recorder.set_tone(ToneSettings { volume: 0.0, ..tone() });
+        recorder.enable_sound(0);
+        recorder.finish(1).unwrap();
+
+        let (_, samples) = samples(wav.into_inner());
+        assert!(samples.iter().all(|s| *s == 0));
+    }
+
+    #[test]
+    fn recorded_device_keeps_the_silence_after_the_last_beep() {
+        let path = std::env::temp_dir().join(format!("chip8-recorded-device-{}.wav", std::process::id()));
+        let options = Options {
+            record_audio: Some(path.to_str().unwrap().to_string()),
+            record_sample_rate: SAMPLE_RATE,
+            tone: tone(),
+            ..Options::parse(["rom.ch8".to_string()]).unwrap()
+        };
+
+        let mut device = RecordedDevice::new(NullSoundDevice::new(), &options).unwrap();
+        device.enable_sound(1);
+        device.disable_sound(2);
+        device.finish(10).unwrap();
+
+        let (_, samples) = samples(std::fs::read(&path).unwrap());
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(samples.len(), SAMPLES_PER_FRAME * 10);
+    }
+}
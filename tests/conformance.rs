@@ -0,0 +1,262 @@
+// Runs test ROMs from tests/roms headlessly and compares the final framebuffer with the golden
+// images in tests/golden, reporting pass/fail per checked screen region.
+//
+// The community test ROMs (Timendus' chip8-test-suite) are not bundled with the sources, see
+// tests/roms/README.md for where to get them. Their cases are #[ignore]d and run with
+// `cargo test --test conformance -- --ignored` once the ROMs are in place; a missing ROM fails.
+// Their golden images have to be transcribed from the suite's documented passing screens, so
+// UPDATE_GOLDEN=1 only (re)writes the golden images of our own ROMs.
+// A failing case leaves its framebuffer as text and as a PNG in target/tmp/conformance.
+
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use chip8::capture::{save_png, DEFAULT_SCALE};
+use chip8::constants::{SCREEN_WIDTH, SCREEN_HEIGHT};
+use chip8::cpu::Cpu;
+use chip8::font::FontSet;
+use chip8::frame::run_frame;
+use chip8::headless_screen::HeadlessScreen;
+use chip8::null_sound_device::NullSoundDevice;
+use chip8::palette::CLASSIC_GREEN;
+use chip8::rom::{Rom, DEFAULT_LOAD_ADDRESS};
+
+// A named part of the screen that gets its own pass/fail line, x/y/width/height in pixels
+struct Region {
+    name: &'static str,
+    x: usize,
+    y: usize,
+    width: usize,
+    height: usize,
+}
+
+const WHOLE_SCREEN: Region = Region { name: "screen", x: 0, y: 0, width: SCREEN_WIDTH, height: SCREEN_HEIGHT };
+
+// Keypad key held down from frame `from` up to (not including) frame `to`
+struct KeyPress {
+    key: usize,
+    from: usize,
+    to: usize,
+}
+
+struct Case {
+    name: &'static str,
+    rom: &'static str,
+    frames: usize,
+    // Bytes written to RAM after loading, the Timendus ROMs read 0x1FF to skip their menus
+    pokes: &'static [(usize, u8)],
+    keys: &'static [KeyPress],
+    regions: &'static [Region],
+    // Whether UPDATE_GOLDEN may record the golden image from our own output. Only for ROMs whose
+    // screen is known without running them, a recorded image can't catch a regression otherwise
+    recordable: bool,
+}
+
+fn run_case(case: &Case) {
+    let rom_path = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/roms").join(case.rom);
+    let rom = fs::read(&rom_path).unwrap_or_else(|_| {
+        panic!("{}: {} not found, see tests/roms/README.md", case.name, rom_path.display())
+    });
+
+    let screen = run_rom(&rom, case);
+    let actual = to_text(screen.framebuffer());
+
+    let golden_path = golden_path(case);
+    if env::var_os("UPDATE_GOLDEN").is_some() {
+        if !case.recordable {
+            println!("{}: golden image is transcribed from the suite, not recorded", case.name);
+            return;
+        }
+
+        fs::create_dir_all(golden_path.parent().unwrap()).unwrap();
+        fs::write(&golden_path, &actual).unwrap();
+        println!("{}: golden image written to {}", case.name, golden_path.display());
+        return;
+    }
+
+    let expected = fs::read_to_string(&golden_path).unwrap_or_else(|_| {
+        panic!("{}: no golden image at {}, see tests/roms/README.md", case.name, golden_path.display())
+    });
+    let expected = from_text(&expected);
+
+    let mut failed = vec![];
+    for region in case.regions {
+        let passed = region_matches(region, screen.framebuffer(), &expected);
+        println!("{}: {} {}", case.name, region.name, if passed { "pass" } else { "FAIL" });
+
+        if !passed {
+            failed.push(region.name);
+        }
+    }
+
+    if !failed.is_empty() {
+        let actual_path = write_actual(case, &actual, screen.framebuffer());
+        panic!("{}: {} failed\nactual framebuffer written to {}\n{actual}", case.name, failed.join(", "), actual_path.display());
+    }
+}
+
+fn run_rom(rom: &[u8], case: &Case) -> HeadlessScreen {
+    let rom = Rom::from_bytes(rom.to_vec(), DEFAULT_LOAD_ADDRESS).unwrap();
+
+    let mut cpu = Cpu::new();
+    cpu.load_font(FontSet::default());
+    cpu.load(&rom).unwrap();
+
+    for (address, value) in case.pokes {
+        cpu.ram[*address] = *value;
+    }
+
+    let mut screen = HeadlessScreen::new();
+    let mut audio = NullSoundDevice::new();
+    let mut last_key = 255;
+
+    for frame in 0..case.frames {
+        let mut pressed_keys = [0; 16];
+        for press in case.keys {
+            if (press.from..press.to).contains(&frame) {
+                pressed_keys[press.key] = 1;
+                if frame == press.from {
+                    last_key = press.key as u8;
+                }
+            }
+            if frame == press.to {
+                last_key = 255;
+            }
+        }
+
+        if let Err(e) = run_frame(&mut cpu, &mut screen, &mut audio, &pressed_keys, &mut last_key) {
+            panic!("{}: stopped at frame {frame}: {e}", case.name);
+        }
+    }
+
+    screen
+}
+
+fn region_matches(region: &Region, actual: &[u8], expected: &[u8]) -> bool {
+    (region.y..region.y + region.height).all(|y| {
+        let row = y * SCREEN_WIDTH;
+        let columns = row + region.x..row + region.x + region.width;
+
+        actual[columns.clone()].iter().map(|p| *p > 0).eq(expected[columns].iter().map(|p| *p > 0))
+    })
+}
+
+fn golden_path(case: &Case) -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/golden").join(format!("{}.txt", case.name))
+}
+
+fn write_actual(case: &Case, actual: &str, fb: &[u8]) -> PathBuf {
+    let dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join("conformance");
+    fs::create_dir_all(&dir).unwrap();
+
+    let path = dir.join(format!("{}.actual.txt", case.name));
+    fs::write(&path, actual).unwrap();
+    save_png(dir.join(format!("{}.actual.png", case.name)), fb, &CLASSIC_GREEN, DEFAULT_SCALE).unwrap();
+
+    path
+}
+
+// Golden images are plain text, one line per row, '#' for a lit pixel and '.' for an unlit one
+fn to_text(fb: &[u8]) -> String {
+    fb.chunks(SCREEN_WIDTH)
+        .map(|row| row.iter().map(|p| if *p > 0 { '#' } else { '.' }).collect::<String>() + "\n")
+        .collect()
+}
+
+fn from_text(text: &str) -> Vec<u8> {
+    let fb: Vec<u8> = text.lines().flat_map(|line| line.chars().map(|c| u8::from(c == '#'))).collect();
+    assert_eq!(fb.len(), SCREEN_WIDTH * SCREEN_HEIGHT, "golden image has the wrong size");
+
+    fb
+}
+
+// Result rows of the quirks test's CHIP-8 results screen, one per text line
+const QUIRK_REGIONS: [Region; 6] = [
+    Region { name: "vF reset", x: 0, y: 0, width: SCREEN_WIDTH, height: 6 },
+    Region { name: "memory", x: 0, y: 6, width: SCREEN_WIDTH, height: 5 },
+    Region { name: "display wait", x: 0, y: 11, width: SCREEN_WIDTH, height: 5 },
+    Region { name: "clipping", x: 0, y: 16, width: SCREEN_WIDTH, height: 5 },
+    Region { name: "shifting", x: 0, y: 21, width: SCREEN_WIDTH, height: 5 },
+    Region { name: "jumping", x: 0, y: 26, width: SCREEN_WIDTH, height: 6 },
+];
+
+#[test]
+fn hex_font() {
+    run_case(&Case {
+        name: "hex-font",
+        rom: "hex-font.ch8",
+        frames: 30,
+        pokes: &[],
+        keys: &[],
+        regions: &[WHOLE_SCREEN],
+        recordable: true,
+    });
+}
+
+#[test]
+#[ignore = "needs tests/roms/3-corax+.ch8, see tests/roms/README.md"]
+fn corax_plus() {
+    run_case(&Case {
+        name: "corax+",
+        rom: "3-corax+.ch8",
+        frames: 120,
+        pokes: &[],
+        keys: &[],
+        regions: &[WHOLE_SCREEN],
+        recordable: false,
+    });
+}
+
+#[test]
+#[ignore = "needs tests/roms/4-flags.ch8, see tests/roms/README.md"]
+fn flags() {
+    run_case(&Case {
+        name: "flags",
+        rom: "4-flags.ch8",
+        frames: 120,
+        pokes: &[],
+        keys: &[],
+        regions: &[
+            Region { name: "happy path", x: 0, y: 0, width: SCREEN_WIDTH, height: 11 },
+            Region { name: "carry", x: 0, y: 11, width: SCREEN_WIDTH, height: 10 },
+            Region { name: "other", x: 0, y: 21, width: SCREEN_WIDTH, height: 11 },
+        ],
+        recordable: false,
+    });
+}
+
+#[test]
+#[ignore = "needs tests/roms/5-quirks.ch8, see tests/roms/README.md"]
+fn quirks() {
+    run_case(&Case {
+        name: "quirks",
+        rom: "5-quirks.ch8",
+        frames: 600,
+        // Platform selection: 1 = CHIP-8
+        pokes: &[(0x1FF, 1)],
+        keys: &[],
+        regions: &QUIRK_REGIONS,
+        recordable: false,
+    });
+}
+
+#[test]
+#[ignore = "needs tests/roms/6-keypad.ch8, see tests/roms/README.md"]
+fn keypad() {
+    run_case(&Case {
+        name: "keypad",
+        rom: "6-keypad.ch8",
+        frames: 120,
+        // Test selection: 1 = EX9E DOWN, shows every key that is held down
+        pokes: &[(0x1FF, 1)],
+        keys: &[
+            KeyPress { key: 0x1, from: 30, to: 120 },
+            KeyPress { key: 0x5, from: 40, to: 120 },
+            KeyPress { key: 0xA, from: 50, to: 120 },
+            KeyPress { key: 0xF, from: 60, to: 120 },
+        ],
+        regions: &[WHOLE_SCREEN],
+        recordable: false,
+    });
+}